@@ -0,0 +1,345 @@
+//===----------------------------------------------------------------------===//
+// bot.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::chat::{ChatAdapter, IncomingMessage, Reply};
+use crate::clock::Clock;
+use crate::config::{Access, GuildConfig, DEFAULT_PREFIX};
+use crate::guilds::GuildCollection;
+use crate::process;
+use crate::reminder::{deliver, ReminderScheduler};
+use crate::storage::Storage;
+use std::sync::{Arc, Mutex};
+
+/// The part of the bot which does not depend on the chat platform.
+/// Keeps the schedules of every guild, and saves them whenever they change.
+/// Messages may be handled from several threads, but only one at a time.
+pub struct Bot {
+    guilds: Mutex<GuildCollection>,
+    storage: Arc<dyn Storage>,
+}
+
+impl Bot {
+    /// Creates a bot with the loaded data, which saves to the given storage.
+    pub fn new(guilds: GuildCollection, storage: Arc<dyn Storage>) -> Bot {
+        Bot {
+            guilds: Mutex::new(guilds),
+            storage,
+        }
+    }
+
+    /// Handles a message, and returns the replies.
+    /// Only messages beginning with the prefix of the guild are queries,
+    /// anything else is ignored.
+    /// The data is saved before replying.
+    pub fn handle(&self, message: &IncomingMessage) -> Vec<Reply> {
+        let mut guilds = self.guilds.lock().unwrap();
+        let query = match guilds.scope(message.guild_id) {
+            Some(schedule) => schedule.config().strip_prefix(&message.content),
+            None => GuildConfig::default().strip_prefix(&message.content),
+        };
+        match query {
+            Some(query) => self
+                .run(&mut guilds, message, query)
+                .into_iter()
+                .map(|content| Reply {
+                    channel_id: message.channel_id,
+                    content,
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Handles a message which is known to be a query, eg. a slash command,
+    /// so it does not begin with a prefix, and returns the reply, if any.
+    /// The data is saved before replying.
+    pub fn handle_command(&self, message: &IncomingMessage) -> Option<String> {
+        let mut guilds = self.guilds.lock().unwrap();
+        self.run(&mut guilds, message, &message.content)
+    }
+
+    /// Handles the query in the message, after expanding any alias of the guild,
+    /// and saves the guild.
+    fn run(
+        &self,
+        guilds: &mut GuildCollection,
+        message: &IncomingMessage,
+        query: &str,
+    ) -> Option<String> {
+        let access = match (message.guild_id, message.manages_guild) {
+            (None, _) => Access::DirectMessage,
+            (Some(_), true) => Access::Manager,
+            (Some(_), false) => Access::Member,
+        };
+        let schedule = guilds.scope_mut(message.guild_id);
+        let query = schedule.config().expand(query);
+        let reply = process::respond(
+            schedule,
+            message.author.id,
+            &message.author.name,
+            message.author.nickname.as_deref(),
+            message.channel_id,
+            access,
+            &query,
+        );
+        if let Err(why) = self.storage.save_scope(guilds, message.guild_id) {
+            eprintln!("Error saving data: {}", why);
+        }
+        reply
+    }
+
+    /// Returns the prefix queries begin with in the guild,
+    /// or in direct messages if the guild is None.
+    pub fn prefix(&self, guild_id: Option<u64>) -> String {
+        let guilds = self.guilds.lock().unwrap();
+        match guilds.scope(guild_id) {
+            Some(schedule) => schedule.config().prefix().to_string(),
+            None => DEFAULT_PREFIX.to_string(),
+        }
+    }
+
+    /// Returns the names of every registered user in the guild,
+    /// or in direct messages if the guild is None, eg. to suggest them.
+    pub fn user_names(&self, guild_id: Option<u64>) -> Vec<String> {
+        let guilds = self.guilds.lock().unwrap();
+        let mut names = guilds
+            .scope(guild_id)
+            .map(|schedule| {
+                schedule
+                    .users()
+                    .values()
+                    .map(|user| user.name())
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Handles a message, and sends the replies through the adapter.
+    pub fn respond(&self, message: &IncomingMessage, adapter: &dyn ChatAdapter) {
+        for reply in self.handle(message) {
+            if let Err(why) = adapter.send(reply.channel_id, &reply.content) {
+                eprintln!("Error sending message: {}", why);
+            }
+        }
+    }
+
+    /// Sends every reminder which is due through the adapter.
+    /// The data is saved before sending, so that reminders are never sent twice.
+    pub fn send_reminders<C: Clock>(
+        &self,
+        scheduler: &ReminderScheduler<C>,
+        adapter: &dyn ChatAdapter,
+    ) {
+        let reminders = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let reminders = scheduler.poll(&mut guilds);
+            if !reminders.is_empty() {
+                if let Err(why) = self.storage.save(&guilds) {
+                    eprintln!("Error saving data: {}", why);
+                }
+            }
+            reminders
+        };
+        reminders
+            .iter()
+            .for_each(|reminder| deliver(reminder, adapter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::{Author, MockAdapter};
+    use crate::clock::ManualClock;
+    use crate::storage::MemoryStorage;
+    use chrono::{Duration, Utc};
+
+    /// Only alice (1) manages the guilds.
+    fn message(author: u64, name: &str, guild_id: Option<u64>, content: &str) -> IncomingMessage {
+        IncomingMessage {
+            author: Author {
+                id: author,
+                name: name.to_string(),
+                nickname: None,
+            },
+            guild_id,
+            channel_id: 50,
+            manages_guild: author == 1,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_respond() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage.clone());
+        let adapter = MockAdapter::default();
+
+        bot.respond(&message(1, "alice", Some(10), "hello"), &adapter);
+        bot.respond(&message(1, "alice", Some(10), "?name Al"), &adapter);
+        assert!(adapter.take().is_empty());
+        assert!(storage
+            .load()
+            .unwrap()
+            .scope(Some(10))
+            .unwrap()
+            .user(1)
+            .is_some());
+
+        bot.respond(&message(2, "bob", Some(10), "?view al"), &adapter);
+        let sent = adapter.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "#50");
+        assert!(sent[0].1.starts_with("```"));
+
+        // Other guilds cannot see the schedule.
+        assert_eq!(
+            bot.handle(&message(2, "bob", Some(20), "?view al")),
+            vec![Reply {
+                channel_id: 50,
+                content: "Could not find the user \"al\"\n".to_string()
+                    + "Usage: ?view <user>, eg. ?view @alice or ?view alice"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_prefix_and_aliases() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage.clone());
+        bot.handle(&message(1, "alice", Some(10), "?add mon from 18 to 20"));
+
+        assert_eq!(
+            bot.handle(&message(1, "alice", Some(10), "?config prefix !")),
+            vec![Reply {
+                channel_id: 50,
+                content: "Queries now begin with !, eg. !help".to_string()
+            }]
+        );
+        assert_eq!(bot.prefix(Some(10)), "!");
+        assert!(bot
+            .handle(&message(1, "alice", Some(10), "?available mon 18"))
+            .is_empty());
+        bot.handle(&message(
+            1,
+            "alice",
+            Some(10),
+            "!config alias free available",
+        ));
+        let reply = bot.handle(&message(2, "bob", Some(10), "!Free mon 18"));
+        assert!(reply[0].content.contains("Mon at 18:00: alice"));
+        let reply = bot.handle(&message(2, "bob", Some(10), "!help"));
+        assert!(reply[0]
+            .content
+            .contains("!add from mon to thu from 1 to 5"));
+        assert!(reply[0].content.contains("- !free runs !available"));
+        let reply = bot.handle(&message(2, "bob", Some(10), "!ad mon"));
+        assert!(reply[0]
+            .content
+            .ends_with("Type !help to see every command"));
+
+        // Other guilds, and direct messages, keep the default prefix.
+        assert_eq!(bot.prefix(Some(20)), "?");
+        assert_eq!(bot.handle(&message(2, "bob", None, "?view")).len(), 1);
+        assert!(bot.handle(&message(2, "bob", None, "!view")).is_empty());
+
+        // The config is saved with the guild.
+        let saved = storage.load().unwrap();
+        let config = saved.scope(Some(10)).unwrap().config();
+        assert_eq!(config.prefix(), "!");
+        assert_eq!(config.expand("free"), "available");
+    }
+
+    #[test]
+    fn test_config_access() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage);
+        assert_eq!(
+            bot.handle(&message(2, "bob", Some(10), "?config prefix !"))[0].content,
+            "Only members who can manage the server can change its config\n".to_string()
+                + "Usage: ?config prefix <prefix>, eg. ?config prefix !"
+        );
+        assert!(
+            bot.handle(&message(2, "bob", Some(10), "?config alias free available"))[0]
+                .content
+                .starts_with("Only members who can manage the server")
+        );
+
+        // Direct messages share a config, which nobody may change.
+        assert!(
+            bot.handle(&message(1, "alice", None, "?config prefix !"))[0]
+                .content
+                .starts_with("The config can only be changed in a server\n")
+        );
+        assert_eq!(bot.prefix(Some(10)), "?");
+        assert_eq!(bot.prefix(None), "?");
+        assert_eq!(bot.handle(&message(2, "bob", None, "?help")).len(), 1);
+    }
+
+    #[test]
+    fn test_period_access() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage);
+        assert!(
+            bot.handle(&message(2, "bob", Some(10), "?period define late 22-2"))[0]
+                .content
+                .starts_with("Only members who can manage the server")
+        );
+        assert!(
+            bot.handle(&message(1, "alice", None, "?period define late 22-2"))[0]
+                .content
+                .starts_with("The config can only be changed in a server\n")
+        );
+        assert_eq!(
+            bot.handle(&message(1, "alice", Some(10), "?period define late 22-2"))[0].content,
+            "late now means 22:00-03:00"
+        );
+
+        // The period is only read where times are expected.
+        bot.handle(&message(2, "bob", Some(10), "?name late"));
+        assert_eq!(bot.user_names(Some(10)), vec!["alice", "late"]);
+        assert_eq!(bot.handle(&message(2, "bob", Some(10), "?help")).len(), 1);
+    }
+
+    #[test]
+    fn test_send_reminders() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage.clone());
+        let adapter = MockAdapter::default();
+        let start = Utc::now() + Duration::days(1);
+        let query = format!(
+            "?event create \"Raid night\" {} {} 1h",
+            start.format("%Y-%m-%d"),
+            start.format("%H:%M")
+        );
+        bot.respond(&message(1, "alice", Some(10), &query), &adapter);
+        bot.respond(
+            &message(1, "alice", Some(10), "?event rsvp 1 yes"),
+            &adapter,
+        );
+        adapter.take();
+
+        let scheduler = ReminderScheduler::new(ManualClock::new(Utc::now()));
+        bot.send_reminders(&scheduler, &adapter);
+        assert!(adapter.take().is_empty());
+
+        scheduler.clock().advance(24 * 60 - 10);
+        bot.send_reminders(&scheduler, &adapter);
+        let sent = adapter.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "#50");
+        assert!(sent[0].1.starts_with("Reminder: Raid night starts in"));
+        assert!(sent[0].1.ends_with("\n@1"));
+
+        // The reminder was saved as sent.
+        let mut saved = storage.load().unwrap();
+        assert!(scheduler.poll(&mut saved).is_empty());
+    }
+}
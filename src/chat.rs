@@ -0,0 +1,93 @@
+//===----------------------------------------------------------------------===//
+// chat.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+
+/// The author of a message, as known by the chat platform.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Author {
+    /// The unique id of the author, which never changes.
+    pub id: u64,
+    /// The name of the author's account, which others may refer to them by.
+    pub name: String,
+    /// The author's nickname in the guild, if they have one.
+    pub nickname: Option<String>,
+}
+
+/// A message received from a chat platform.
+/// Ids are whatever the platform uses to tell guilds (servers), channels and users apart.
+#[derive(Clone, PartialEq, Debug)]
+pub struct IncomingMessage {
+    /// Who sent the message.
+    pub author: Author,
+    /// The guild the message was sent in, or None for a direct message.
+    pub guild_id: Option<u64>,
+    /// The channel the message was sent in, where replies are sent.
+    pub channel_id: u64,
+    /// Whether the author may manage the guild, eg. change the prefix of queries.
+    /// Ignored in direct messages.
+    pub manages_guild: bool,
+    /// The text of the message.
+    pub content: String,
+}
+
+/// A message for the chat platform to send to a channel.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Reply {
+    /// The channel to send the reply in.
+    pub channel_id: u64,
+    /// The text of the reply.
+    pub content: String,
+}
+
+/// Connects the bot to a chat platform, eg. Discord.
+/// The adapter receives messages from the platform and passes them to Bot::respond,
+/// which sends the replies, along with reminders, through the adapter.
+pub trait ChatAdapter {
+    /// Sends a message to a channel.
+    fn send(&self, channel: u64, message: &str) -> Result<(), String>;
+
+    /// Sends a message directly to a user.
+    fn send_direct(&self, user: u64, message: &str) -> Result<(), String>;
+
+    /// Returns how a user is mentioned in a message, so that they are notified.
+    fn mention(&self, user: u64) -> String {
+        "@".to_string() + &user.to_string()
+    }
+}
+
+/// Keeps every message in memory, along with where it was sent, eg. "#50" or "@2".
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockAdapter {
+    pub sent: std::cell::RefCell<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+impl MockAdapter {
+    /// Returns every message sent so far, and forgets them.
+    pub fn take(&self) -> Vec<(String, String)> {
+        self.sent.borrow_mut().drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+impl ChatAdapter for MockAdapter {
+    fn send(&self, channel: u64, message: &str) -> Result<(), String> {
+        self.sent
+            .borrow_mut()
+            .push(("#".to_string() + &channel.to_string(), message.to_string()));
+        Ok(())
+    }
+
+    fn send_direct(&self, user: u64, message: &str) -> Result<(), String> {
+        self.sent
+            .borrow_mut()
+            .push(("@".to_string() + &user.to_string(), message.to_string()));
+        Ok(())
+    }
+}
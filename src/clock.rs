@@ -0,0 +1,53 @@
+//===----------------------------------------------------------------------===//
+// clock.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use chrono::{DateTime, Utc};
+
+/// A source of the current moment.
+/// Allows anything which depends on the time to be tested without waiting.
+pub trait Clock: Send + Sync {
+    /// Returns the current moment.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The actual time, according to the system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock which only moves when it is told to.
+#[cfg(test)]
+pub struct ManualClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> ManualClock {
+        ManualClock {
+            now: std::sync::Mutex::new(now),
+        }
+    }
+
+    /// Moves the clock forward by some minutes.
+    pub fn advance(&self, minutes: i64) {
+        let mut now = self.now.lock().unwrap();
+        *now += chrono::Duration::minutes(minutes);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
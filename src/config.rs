@@ -0,0 +1,153 @@
+//===----------------------------------------------------------------------===//
+// config.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The prefix queries begin with, unless the guild changed it.
+pub const DEFAULT_PREFIX: &str = "?";
+
+/// The longest prefix a guild may use.
+pub const MAX_PREFIX_LEN: usize = 5;
+
+/// What the user who sent a query may do with the config it was sent under.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Access {
+    /// The user manages the guild, so they may change its config.
+    Manager,
+    /// The user may use the bot in the guild, but not change its config.
+    Member,
+    /// The query was sent in direct messages, whose config is shared by every user,
+    /// so nobody may change it.
+    DirectMessage,
+}
+
+/// How the bot is used in a guild, which every guild may change,
+/// eg. when the default prefix is already used by another bot.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct GuildConfig {
+    prefix: String,
+    /// Other names for commands, eg. free for available, by the alias.
+    aliases: BTreeMap<String, String>,
+    /// Names for ranges of times (in minutes), eg. raidtime for 19:00-24:00, by the name.
+    periods: BTreeMap<String, (u32, u32)>,
+}
+
+impl Default for GuildConfig {
+    fn default() -> GuildConfig {
+        GuildConfig {
+            prefix: DEFAULT_PREFIX.to_string(),
+            aliases: BTreeMap::new(),
+            periods: BTreeMap::new(),
+        }
+    }
+}
+
+impl GuildConfig {
+    /// Retrieves the prefix queries begin with.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Sets the prefix queries begin with.
+    pub fn set_prefix(&mut self, prefix: String) {
+        self.prefix = prefix;
+    }
+
+    /// Retrieves every alias, along with the command it runs.
+    pub fn aliases(&self) -> &BTreeMap<String, String> {
+        &self.aliases
+    }
+
+    /// Sets the command an alias runs, or removes the alias if there is none.
+    /// Returns the command the alias ran before, if any.
+    pub fn set_alias(&mut self, alias: String, command: Option<String>) -> Option<String> {
+        match command {
+            Some(command) => self.aliases.insert(alias, command),
+            None => self.aliases.remove(&alias),
+        }
+    }
+
+    /// Retrieves every period defined in the guild, along with its range of times.
+    pub fn periods(&self) -> &BTreeMap<String, (u32, u32)> {
+        &self.periods
+    }
+
+    /// Sets the range of times a period refers to, or removes the period if there is none.
+    /// Returns the range the period referred to before, if any.
+    pub fn set_period(&mut self, name: String, range: Option<(u32, u32)>) -> Option<(u32, u32)> {
+        match range {
+            Some(range) => self.periods.insert(name, range),
+            None => self.periods.remove(&name),
+        }
+    }
+
+    /// Returns the query in the message, without the prefix,
+    /// or None if the message is not a query.
+    pub fn strip_prefix<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_prefix(self.prefix.as_str())
+    }
+
+    /// Replaces an alias at the start of the query with the command it runs,
+    /// eg. "free mon" becomes "available mon".
+    pub fn expand(&self, query: &str) -> String {
+        let query = query.trim_start();
+        let (first, rest) = match query.find(char::is_whitespace) {
+            Some(idx) => query.split_at(idx),
+            None => (query, ""),
+        };
+        match self.aliases.get(&first.to_lowercase()) {
+            Some(command) => command.clone() + rest,
+            None => query.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_and_aliases() {
+        let mut config = GuildConfig::default();
+        assert_eq!(config.strip_prefix("?add mon 18"), Some("add mon 18"));
+        assert_eq!(config.strip_prefix("!add mon 18"), None);
+
+        config.set_prefix("!".to_string());
+        assert_eq!(config.strip_prefix("!add mon 18"), Some("add mon 18"));
+        assert_eq!(config.strip_prefix("?add mon 18"), None);
+
+        assert_eq!(
+            config.set_alias("free".to_string(), Some("available".to_string())),
+            None
+        );
+        config.set_alias("raids".to_string(), Some("event list".to_string()));
+        assert_eq!(config.expand("Free mon 18"), "available mon 18");
+        assert_eq!(config.expand("raids"), "event list");
+        assert_eq!(config.expand("freedom mon"), "freedom mon");
+        assert_eq!(
+            config.set_alias("free".to_string(), None),
+            Some("available".to_string())
+        );
+        assert_eq!(config.expand("free mon 18"), "free mon 18");
+
+        config.set_period("raidtime".to_string(), Some((19 * 60, 24 * 60)));
+        config.set_period("late".to_string(), Some((22 * 60 + 30, 2 * 60)));
+        assert_eq!(config.periods().get("late"), Some(&(22 * 60 + 30, 2 * 60)));
+        assert_eq!(
+            config.set_period("raidtime".to_string(), None),
+            Some((19 * 60, 24 * 60))
+        );
+        assert!(!config.periods().contains_key("raidtime"));
+
+        // Guilds saved before they had a config use the defaults.
+        let config: GuildConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, GuildConfig::default());
+    }
+}
@@ -6,6 +6,7 @@
 // Copyright (c) 2020 Philippe Nadon
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
 use std::fmt;
 
 /// Represents the days of the week.
@@ -40,3 +41,84 @@ pub fn num_to_day(num: u32) -> Option<Day> {
         _ => None,
     }
 }
+
+/// Used for converting a chrono weekday into the corresponding day of the week.
+pub fn weekday_to_day(weekday: Weekday) -> Day {
+    num_to_day(weekday.num_days_from_sunday()).unwrap()
+}
+
+/// Returns every day in the range (inclusive), in order.
+/// If the range is from a later day to an earlier day, it wraps around the week.
+/// Eg., from Fri to Tue.
+pub fn day_range(start_day: Day, end_day: Day) -> Vec<Day> {
+    let end_num = if end_day < start_day {
+        end_day as u32 + 7
+    } else {
+        end_day as u32
+    };
+
+    ((start_day as u32)..=end_num)
+        .map(|day_num| num_to_day(day_num % 7).unwrap())
+        .collect()
+}
+
+/// A date relative to the day a query is sent, eg. tomorrow or next fri.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum RelativeDate {
+    Today,
+    /// Today, from the evening on.
+    Tonight,
+    Tomorrow,
+    /// The next time the day occurs, after today.
+    Next(Day),
+    /// The coming Sat and Sun, or the rest of the weekend if it has already begun.
+    ThisWeekend,
+}
+
+impl RelativeDate {
+    /// Returns the dates it refers to, in order, given the date it is today.
+    pub fn resolve(self, today: NaiveDate) -> Vec<NaiveDate> {
+        let days_ahead =
+            |day: Day| (day as i64 - weekday_to_day(today.weekday()) as i64).rem_euclid(7);
+        match self {
+            RelativeDate::Today | RelativeDate::Tonight => vec![today],
+            RelativeDate::Tomorrow => vec![today + Duration::days(1)],
+            RelativeDate::Next(day) => match days_ahead(day) {
+                0 => vec![today + Duration::days(7)],
+                days => vec![today + Duration::days(days)],
+            },
+            RelativeDate::ThisWeekend => match weekday_to_day(today.weekday()) {
+                Day::Sun => vec![today],
+                _ => {
+                    let sat = today + Duration::days(days_ahead(Day::Sat));
+                    vec![sat, sat + Duration::days(1)]
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_dates() {
+        let wed = NaiveDate::from_ymd_opt(2026, 11, 4).unwrap();
+        let date = |day: u32| NaiveDate::from_ymd_opt(2026, 11, day).unwrap();
+        assert_eq!(RelativeDate::Tonight.resolve(wed), vec![wed]);
+        assert_eq!(RelativeDate::Tomorrow.resolve(wed), vec![date(5)]);
+        assert_eq!(RelativeDate::Next(Day::Fri).resolve(wed), vec![date(6)]);
+        assert_eq!(RelativeDate::Next(Day::Wed).resolve(wed), vec![date(11)]);
+        assert_eq!(RelativeDate::Next(Day::Mon).resolve(wed), vec![date(9)]);
+        assert_eq!(
+            RelativeDate::ThisWeekend.resolve(wed),
+            vec![date(7), date(8)]
+        );
+        assert_eq!(
+            RelativeDate::ThisWeekend.resolve(date(7)),
+            vec![date(7), date(8)]
+        );
+        assert_eq!(RelativeDate::ThisWeekend.resolve(date(8)), vec![date(8)]);
+    }
+}
@@ -0,0 +1,163 @@
+//===----------------------------------------------------------------------===//
+// discord.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::bot::Bot;
+use crate::chat::{Author, ChatAdapter, IncomingMessage};
+use crate::clock::SystemClock;
+use crate::reminder::{ReminderScheduler, POLL_INTERVAL};
+use crate::slash::{self, Interaction};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+use serenity::{
+    http::Http,
+    model::{
+        channel::Message,
+        gateway::{Activity, Ready},
+        id::{ChannelId, UserId},
+    },
+    prelude::*,
+};
+
+/// Whether the reminder scheduler was started,
+/// since the bot may become ready again after reconnecting.
+static REMINDERS_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Observes and handles events.
+struct Handler {
+    bot: Arc<Bot>,
+}
+
+/// Sends messages to Discord.
+struct DiscordAdapter {
+    http: Arc<Http>,
+}
+
+impl ChatAdapter for DiscordAdapter {
+    fn send(&self, channel: u64, message: &str) -> Result<(), String> {
+        ChannelId(channel)
+            .say(&self.http, message)
+            .map(|_| ())
+            .map_err(|why| why.to_string())
+    }
+
+    fn send_direct(&self, user: u64, message: &str) -> Result<(), String> {
+        UserId(user)
+            .create_dm_channel(&self.http)
+            .and_then(|channel| channel.say(&self.http, message))
+            .map(|_| ())
+            .map_err(|why| why.to_string())
+    }
+
+    fn mention(&self, user: u64) -> String {
+        "<@".to_string() + &user.to_string() + ">"
+    }
+}
+
+impl EventHandler for Handler {
+    /// Passes incoming messages to the bot, which ignores anything without
+    /// the prefix of the guild, and sends its replies.
+    /// Messages from bots are ignored, so that the bot never replies to itself.
+    fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot {
+            return;
+        }
+        let message = IncomingMessage {
+            author: Author {
+                id: *msg.author.id.as_u64(),
+                name: msg.author.name.clone(),
+                nickname: msg.author_nick(&ctx),
+            },
+            guild_id: msg.guild_id.map(|guild_id| *guild_id.as_u64()),
+            channel_id: *msg.channel_id.as_u64(),
+            manages_guild: msg.guild(&ctx.cache).is_some_and(|guild| {
+                guild
+                    .read()
+                    .member_permissions(msg.author.id)
+                    .manage_guild()
+            }),
+            content: msg.content,
+        };
+        let adapter = DiscordAdapter {
+            http: ctx.http.clone(),
+        };
+        self.bot.respond(&message, &adapter);
+    }
+
+    /// Executes when the bot first starts.
+    /// Starts sending reminders for events in the background.
+    fn ready(&self, ctx: Context, ready: Ready) {
+        ctx.set_activity(Activity::playing("Type \"?help\" to get started!"));
+        println!("{} is connected!", ready.user.name);
+        register_commands(&ctx.http, *ready.user.id.as_u64());
+
+        if !REMINDERS_STARTED.swap(true, Ordering::SeqCst) {
+            let bot = self.bot.clone();
+            let adapter = DiscordAdapter {
+                http: ctx.http.clone(),
+            };
+            thread::spawn(move || run_reminders(bot, adapter));
+        }
+    }
+
+    /// Handles slash commands, which this version of serenity does not know about.
+    fn unknown(&self, _ctx: Context, name: String, raw: Value) {
+        if name != "INTERACTION_CREATE" {
+            return;
+        }
+        let interaction = match Interaction::from_value(raw) {
+            Ok(interaction) => interaction,
+            Err(why) => {
+                eprintln!("Error reading interaction: {}", why);
+                return;
+            }
+        };
+        if let Some(response) = slash::respond(&self.bot, &interaction) {
+            let sent = reqwest::blocking::Client::new()
+                .post(&interaction.callback_url())
+                .json(&response)
+                .send()
+                .and_then(|response| response.error_for_status());
+            if let Err(why) = sent {
+                eprintln!("Error responding to interaction: {}", why);
+            }
+        }
+    }
+}
+
+/// Registers the slash commands, replacing any which were registered before.
+fn register_commands(http: &Http, application_id: u64) {
+    let registered = reqwest::blocking::Client::new()
+        .put(&slash::commands_url(application_id))
+        .header("Authorization", &http.token)
+        .json(&slash::commands())
+        .send()
+        .and_then(|response| response.error_for_status());
+    if let Err(why) = registered {
+        eprintln!("Error registering slash commands: {}", why);
+    }
+}
+
+/// Regularly sends every reminder which is due.
+fn run_reminders(bot: Arc<Bot>, adapter: DiscordAdapter) {
+    let scheduler = ReminderScheduler::new(SystemClock);
+    loop {
+        bot.send_reminders(&scheduler, &adapter);
+        thread::sleep(Duration::from_secs(POLL_INTERVAL));
+    }
+}
+
+/// Connects the bot to Discord, and handles messages until it disconnects.
+pub fn run(bot: Arc<Bot>, token: &str) {
+    let mut client = Client::new(token, Handler { bot }).expect("Could not create client.");
+    client.start().expect("Could not start client.");
+}
@@ -0,0 +1,229 @@
+//===----------------------------------------------------------------------===//
+// error.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::parse::ParamType;
+use std::fmt;
+
+/// Descriptions of the values a query may expect, as shown to the user.
+pub const EXPECTED_DAY: &str = "a day, eg. mon, weekends or from mon to fri";
+pub const EXPECTED_DAYS_OR_TIMES: &str = "days and/or times, eg. mon 18 or weekends from 18 to 23";
+pub const EXPECTED_TIME: &str = "a time, eg. 18, 18:30 or 7pm";
+pub const EXPECTED_TIMES: &str = "times, eg. 18 19, from 18 to 23 or 7pm-11pm";
+pub const EXPECTED_TO: &str = "\"to\"";
+pub const EXPECTED_DATE: &str = "a date, eg. tomorrow, next fri or 2026-11-03";
+pub const EXPECTED_WEEKEND: &str = "\"weekend\"";
+pub const EXPECTED_DURATION: &str = "a duration, eg. 3h, 90m or 1h30m";
+pub const EXPECTED_TIMEZONE: &str = "a timezone, eg. -7, +5:30 or America/Vancouver";
+pub const EXPECTED_USER: &str = "a user, eg. @alice";
+pub const EXPECTED_TITLE: &str = "a title, eg. \"Raid night\"";
+pub const EXPECTED_EVENT_ID: &str = "an event id, eg. 3";
+pub const EXPECTED_RSVP: &str = "a response, either yes, no or maybe";
+pub const EXPECTED_REMINDER: &str = "how long before to remind, eg. 30, 1h or off";
+pub const EXPECTED_DM: &str = "\"dm\"";
+pub const EXPECTED_PREFIX: &str = "a prefix of up to 5 symbols, eg. ! or $$";
+pub const EXPECTED_ALIAS: &str = "a name which is not a command, eg. free";
+pub const EXPECTED_COMMAND: &str = "a command, eg. available";
+pub const EXPECTED_PERIOD: &str = "a range of times, eg. 19-23 or from 7pm to 11pm";
+pub const EXPECTED_PERIOD_NAME: &str = "a name which is not a day, time or command, eg. raidtime";
+
+/// Describes which param of a query could not be parsed, and why.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TokenError {
+    /// The param is not what was expected, eg. a time of 25.
+    Invalid {
+        token: String,
+        expected: &'static str,
+    },
+    /// The query ended before a param which was expected.
+    Missing { expected: &'static str },
+    /// The query continued after every param which was expected.
+    Unexpected(String),
+}
+
+/// Allows the error to be displayed by the bot.
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenError::Invalid { token, expected } => {
+                write!(f, "Expected {}, but found \"{}\"", expected, token)
+            }
+            TokenError::Missing { expected } => {
+                write!(f, "Expected {}, but the message ended", expected)
+            }
+            TokenError::Unexpected(token) => write!(f, "Did not expect \"{}\"", token),
+        }
+    }
+}
+
+/// Reasons a query could not be handled, which are explained to the user.
+#[derive(Clone, PartialEq, Debug)]
+pub enum QueryError {
+    /// The query only contained the prefix.
+    NoCommand,
+    /// The first word of the query is not a command.
+    UnknownCommand(String),
+    /// A param of the command could not be parsed.
+    Syntax(ParamType, TokenError),
+    /// The params were parsed, but the command does not support that combination.
+    Unsupported(ParamType),
+    /// The command only supports a single value, eg. a single day.
+    TooMany(ParamType, &'static str),
+    /// The user who sent the query has not been registered.
+    NotRegistered,
+    /// Another user could not be found, along with a similar name, if there is one.
+    UnknownUser {
+        name: String,
+        suggestion: Option<String>,
+    },
+    /// The event does not exist, or was removed.
+    UnknownEvent(u32),
+    /// Only the creator of the event may change it.
+    NotEventCreator(u32),
+    /// Events cannot be planned in the past.
+    EventInPast,
+    /// The config of direct messages is shared by every user, so it cannot be changed.
+    ConfigInDirectMessages,
+    /// Only those who manage the guild may change its config.
+    NotManager,
+}
+
+impl QueryError {
+    /// Retrieves the command which failed, if it is known.
+    pub fn command(&self) -> Option<ParamType> {
+        match self {
+            QueryError::Syntax(command, _)
+            | QueryError::Unsupported(command)
+            | QueryError::TooMany(command, _) => Some(*command),
+            _ => None,
+        }
+    }
+
+    /// Returns the explanation for the user, along with how to use the command.
+    /// The command is used for the usage hint if the error does not know it.
+    /// Usages are written with {pref} in place of the prefix, which is replaced by the guild's.
+    pub fn reply(&self, command: Option<ParamType>, prefix: &str) -> String {
+        match self {
+            QueryError::NoCommand | QueryError::UnknownCommand(_) => {
+                self.to_string() + "\nType " + prefix + "help to see every command"
+            }
+            _ => match self.command().or(command) {
+                Some(command) => {
+                    self.to_string() + "\nUsage: " + &usage(command).replace("{pref}", prefix)
+                }
+                None => self.to_string(),
+            },
+        }
+    }
+}
+
+/// Allows the error to be displayed by the bot.
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::NoCommand => write!(f, "Expected a command"),
+            QueryError::UnknownCommand(command) => write!(f, "Unknown command \"{}\"", command),
+            QueryError::Syntax(_, why) => write!(f, "{}", why),
+            QueryError::Unsupported(_) => write!(f, "That combination of values is not supported"),
+            QueryError::TooMany(_, expected) => write!(f, "Expected only {}", expected),
+            QueryError::NotRegistered => write!(f, "Could not find your schedule"),
+            QueryError::UnknownUser {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "Could not find the user \"{}\", did you mean \"{}\"?",
+                name, suggestion
+            ),
+            QueryError::UnknownUser {
+                name,
+                suggestion: None,
+            } => write!(f, "Could not find the user \"{}\"", name),
+            QueryError::UnknownEvent(id) => write!(f, "Could not find event #{}", id),
+            QueryError::NotEventCreator(id) => {
+                write!(f, "Only the creator of event #{} can change it", id)
+            }
+            QueryError::EventInPast => write!(f, "Events cannot be planned in the past"),
+            QueryError::ConfigInDirectMessages => {
+                write!(f, "The config can only be changed in a server")
+            }
+            QueryError::NotManager => {
+                write!(
+                    f,
+                    "Only members who can manage the server can change its config"
+                )
+            }
+        }
+    }
+}
+
+/// Returns a short description of how to use the command.
+pub fn usage(command: ParamType) -> &'static str {
+    match command {
+        ParamType::TimeZone => "{pref}timezone <timezone>, eg. {pref}timezone -7 or {pref}timezone America/Vancouver",
+        ParamType::Name => "{pref}name <name>, eg. {pref}name philio",
+        ParamType::AddSchedule => "{pref}add <Day(s)> <time(s)>, eg. {pref}add mon wed from 18 to 23",
+        ParamType::RemoveSchedule => "{pref}remove <Day(s)> <time(s)>, eg. {pref}remove weekends 18 19",
+        ParamType::ViewSchedule => "{pref}view <user>, eg. {pref}view @alice or {pref}view alice",
+        ParamType::Available => "{pref}available <Day or date> <time>, eg. {pref}available mon 15",
+        ParamType::Away => "{pref}away <date> <time(s)>, eg. {pref}away 2026-11-03 from 18 to 23",
+        ParamType::Extra => "{pref}extra <date> <time(s)>, eg. {pref}extra 2026-11-05 20 21",
+        ParamType::Best => "{pref}best <duration> <Day(s)>, eg. {pref}best 3h weekend",
+        ParamType::Overlap => "{pref}overlap <user(s)> <Day(s)>, eg. {pref}overlap @alice @bob weekends",
+        ParamType::CreateEvent => {
+            "{pref}event create <title> <Day or date> <time> <duration>, eg. {pref}event create \"Raid night\" fri 20 3h"
+        }
+        ParamType::ListEvents => "{pref}event list",
+        ParamType::ViewEvent => "{pref}event view <id>, eg. {pref}event view 3",
+        ParamType::Rsvp => "{pref}event rsvp <id> <yes, no or maybe>, eg. {pref}event rsvp 3 yes",
+        ParamType::CancelEvent => "{pref}event cancel <id>, eg. {pref}event cancel 3",
+        ParamType::Remind => "{pref}event remind <id> <duration> dm, eg. {pref}event remind 3 30",
+        ParamType::Meme => "{pref}showtime",
+        ParamType::Help => "{pref}help",
+        ParamType::ViewConfig => "{pref}config",
+        ParamType::Prefix => "{pref}config prefix <prefix>, eg. {pref}config prefix !",
+        ParamType::Alias => "{pref}config alias <alias> <command>, eg. {pref}config alias free available",
+        ParamType::ListPeriods => "{pref}period",
+        ParamType::Period => "{pref}period define <name> <times>, eg. {pref}period define raidtime 19-23",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply() {
+        assert_eq!(
+            QueryError::Syntax(
+                ParamType::AddSchedule,
+                TokenError::Invalid {
+                    token: "25".to_string(),
+                    expected: EXPECTED_TIME
+                }
+            )
+            .reply(None, "?"),
+            "Expected a time, eg. 18, 18:30 or 7pm, but found \"25\"\n".to_string()
+                + "Usage: ?add <Day(s)> <time(s)>, eg. ?add mon wed from 18 to 23"
+        );
+        assert_eq!(
+            QueryError::UnknownEvent(3).reply(Some(ParamType::Rsvp), "?"),
+            "Could not find event #3\n".to_string()
+                + "Usage: ?event rsvp <id> <yes, no or maybe>, eg. ?event rsvp 3 yes"
+        );
+        assert_eq!(
+            QueryError::UnknownCommand("ad".to_string()).reply(None, "?"),
+            "Unknown command \"ad\"\nType ?help to see every command"
+        );
+
+        // The guild's prefix is shown instead of the default one.
+        assert_eq!(
+            QueryError::UnknownEvent(3).reply(Some(ParamType::CancelEvent), "!"),
+            "Could not find event #3\nUsage: !event cancel <id>, eg. !event cancel 3"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//===----------------------------------------------------------------------===//
+// event.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A user's response to an event.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Rsvp {
+    Yes,
+    Maybe,
+    No,
+}
+
+/// Allows the response to be displayed by the bot.
+impl fmt::Display for Rsvp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The number of minutes before an event that a reminder is sent, unless changed.
+pub const DEFAULT_REMINDER: u32 = 15;
+
+/// Represents a single planned session, at a specific moment.
+/// Responses are kept by the unique id of the user.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Event {
+    title: String,
+    creator: u64,
+    start: DateTime<Utc>,
+    duration: u32,
+    rsvps: HashMap<u64, Rsvp>,
+    /// The channel the event was planned in, where reminders are posted.
+    #[serde(default)]
+    channel: Option<u64>,
+    /// How many minutes before the start a reminder is sent, if at all.
+    #[serde(default)]
+    remind_before: Option<u32>,
+    /// Whether the reminder is also sent directly to everyone coming.
+    #[serde(default)]
+    remind_dm: bool,
+    /// Whether the reminder was already sent, so it is not sent twice.
+    #[serde(default)]
+    reminded: bool,
+}
+
+impl Event {
+    pub fn new(title: String, creator: u64, start: DateTime<Utc>, duration: u32) -> Event {
+        Event {
+            title,
+            creator,
+            start,
+            duration,
+            rsvps: HashMap::new(),
+            channel: None,
+            remind_before: None,
+            remind_dm: false,
+            reminded: false,
+        }
+    }
+
+    /// Retrieves the event's title.
+    pub fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    /// Retrieves the unique id of the user who created the event.
+    pub fn creator(&self) -> u64 {
+        self.creator
+    }
+
+    /// Retrieves the moment the event starts.
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    /// Retrieves the moment the event ends.
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + Duration::minutes(self.duration as i64)
+    }
+
+    /// Retrieves the channel reminders are posted in.
+    pub fn channel(&self) -> Option<u64> {
+        self.channel
+    }
+
+    /// Sets the channel reminders are posted in.
+    pub fn set_channel(&mut self, channel: u64) {
+        self.channel = Some(channel);
+    }
+
+    /// Retrieves how many minutes before the start a reminder is sent,
+    /// and whether it is also sent directly to everyone coming.
+    pub fn reminder(&self) -> (Option<u32>, bool) {
+        (self.remind_before, self.remind_dm)
+    }
+
+    /// Sets how many minutes before the start a reminder is sent, if at all.
+    /// The reminder will be sent again, even if it already was.
+    pub fn set_reminder(&mut self, remind_before: Option<u32>, remind_dm: bool) {
+        self.remind_before = remind_before;
+        self.remind_dm = remind_dm;
+        self.reminded = false;
+    }
+
+    /// Checks if the reminder should be sent at that moment.
+    /// Reminders which were missed are still sent, as long as the event has not ended.
+    pub fn reminder_due(&self, at: DateTime<Utc>) -> bool {
+        match (self.remind_before, self.channel) {
+            (Some(remind_before), Some(_)) => {
+                !self.reminded
+                    && at >= self.start - Duration::minutes(remind_before as i64)
+                    && at < self.end()
+            }
+            _ => false,
+        }
+    }
+
+    /// Sets the user's response to the event, replacing any previous response.
+    pub fn set_rsvp(&mut self, id: u64, rsvp: Rsvp) {
+        self.rsvps.insert(id, rsvp);
+    }
+
+    /// Retrieves the unique ids of every user who gave that response.
+    pub fn rsvps(&self, rsvp: Rsvp) -> Vec<u64> {
+        let mut ids = self
+            .rsvps
+            .iter()
+            .filter(|(_, response)| **response == rsvp)
+            .map(|(id, _)| *id)
+            .collect::<Vec<u64>>();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Contains every event in a guild, by their id.
+/// Ids are never reused, so that responses cannot go to the wrong event.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventCollection {
+    events: BTreeMap<u32, Event>,
+    next_id: u32,
+}
+
+impl Default for EventCollection {
+    fn default() -> EventCollection {
+        EventCollection::new()
+    }
+}
+
+impl EventCollection {
+    pub fn new() -> EventCollection {
+        EventCollection {
+            events: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Adds the event, returning its id.
+    /// Events which ended more than a day before that moment are removed.
+    pub fn insert(&mut self, event: Event, at: DateTime<Utc>) -> u32 {
+        self.events
+            .retain(|_, event| event.end() + Duration::days(1) > at);
+        let id = self.next_id;
+        self.events.insert(id, event);
+        self.next_id = id + 1;
+        id
+    }
+
+    /// Removes the event, returning it if it existed.
+    pub fn remove(&mut self, id: u32) -> Option<Event> {
+        self.events.remove(&id)
+    }
+
+    /// Retrieves an immutable reference to an event.
+    pub fn get(&self, id: u32) -> Option<&Event> {
+        self.events.get(&id)
+    }
+
+    /// Retrieves a mutable reference to an event.
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Event> {
+        self.events.get_mut(&id)
+    }
+
+    /// Returns the ids of the events whose reminders are due at that moment,
+    /// marking them as sent.
+    pub fn take_due_reminders(&mut self, at: DateTime<Utc>) -> Vec<u32> {
+        self.events
+            .iter_mut()
+            .filter(|(_, event)| event.reminder_due(at))
+            .map(|(id, event)| {
+                event.reminded = true;
+                *id
+            })
+            .collect()
+    }
+
+    /// Returns the events which have not ended at that moment, in the order they start.
+    pub fn upcoming(&self, at: DateTime<Utc>) -> Vec<(u32, &Event)> {
+        let mut events = self
+            .events
+            .iter()
+            .filter(|(_, event)| event.end() > at)
+            .map(|(id, event)| (*id, event))
+            .collect::<Vec<(u32, &Event)>>();
+        events.sort_by_key(|(id, event)| (event.start(), *id));
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_events() {
+        let now = Utc.with_ymd_and_hms(2026, 11, 2, 12, 0, 0).unwrap();
+        let mut events = EventCollection::new();
+        let raid = events.insert(
+            Event::new("Raid night".to_string(), 1, now + Duration::days(4), 180),
+            now,
+        );
+        let past = events.insert(
+            Event::new("Last week".to_string(), 1, now - Duration::days(7), 60),
+            now,
+        );
+        let soon = events.insert(
+            Event::new("Soon".to_string(), 2, now + Duration::hours(1), 60),
+            now,
+        );
+        assert_eq!((raid, past, soon), (1, 2, 3));
+        assert_eq!(
+            events
+                .upcoming(now)
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<u32>>(),
+            vec![soon, raid]
+        );
+
+        let event = events.get_mut(raid).unwrap();
+        event.set_rsvp(5, Rsvp::Yes);
+        event.set_rsvp(4, Rsvp::Maybe);
+        event.set_rsvp(3, Rsvp::Yes);
+        event.set_rsvp(4, Rsvp::No);
+        assert_eq!(event.rsvps(Rsvp::Yes), vec![3, 5]);
+        assert!(event.rsvps(Rsvp::Maybe).is_empty());
+        assert_eq!(event.rsvps(Rsvp::No), vec![4]);
+
+        // Old events are cleaned up, but their ids are not reused.
+        let next = events.insert(
+            Event::new("Next".to_string(), 1, now + Duration::days(1), 60),
+            now,
+        );
+        assert_eq!(next, 4);
+        assert!(events.get(past).is_none());
+        assert!(events.remove(soon).is_some());
+        assert!(events.get(soon).is_none());
+    }
+
+    #[test]
+    fn test_reminders() {
+        let now = Utc.with_ymd_and_hms(2026, 11, 2, 12, 0, 0).unwrap();
+        let mut events = EventCollection::new();
+        let raid = events.insert(
+            Event::new("Raid night".to_string(), 1, now + Duration::hours(1), 60),
+            now,
+        );
+        let quiet = events.insert(
+            Event::new("Quiet".to_string(), 1, now + Duration::minutes(5), 60),
+            now,
+        );
+        events.get_mut(raid).unwrap().set_channel(10);
+        events.get_mut(raid).unwrap().set_reminder(Some(30), false);
+        events.get_mut(quiet).unwrap().set_reminder(Some(30), false);
+
+        // Without a channel, there is nowhere to send the reminder.
+        assert!(events.take_due_reminders(now).is_empty());
+        assert_eq!(
+            events.take_due_reminders(now + Duration::minutes(30)),
+            vec![raid]
+        );
+        assert!(events
+            .take_due_reminders(now + Duration::minutes(31))
+            .is_empty());
+
+        // Changing the reminder sends it again.
+        events.get_mut(raid).unwrap().set_reminder(Some(10), true);
+        assert!(events
+            .take_due_reminders(now + Duration::minutes(31))
+            .is_empty());
+        assert_eq!(
+            events.take_due_reminders(now + Duration::minutes(55)),
+            vec![raid]
+        );
+
+        // Reminders are not sent once the event is over.
+        events.get_mut(raid).unwrap().set_reminder(Some(10), false);
+        assert!(events
+            .take_due_reminders(now + Duration::hours(2))
+            .is_empty());
+    }
+}
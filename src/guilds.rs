@@ -0,0 +1,167 @@
+//===----------------------------------------------------------------------===//
+// guilds.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::reminder::Reminder;
+use crate::schedules::ScheduleCollection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Contains a separate collection of schedules for every guild (server),
+/// so that users in one guild cannot see the schedules of another.
+/// Direct messages to the bot are kept in their own scope.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GuildCollection {
+    guilds: HashMap<u64, ScheduleCollection>,
+    direct_messages: ScheduleCollection,
+    /// Schedules from before data was isolated by guild.
+    /// They are not shared with any guild, until they are moved into
+    /// the guild they were used in with move_legacy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    legacy: Option<ScheduleCollection>,
+}
+
+impl Default for GuildCollection {
+    fn default() -> GuildCollection {
+        GuildCollection::new()
+    }
+}
+
+impl GuildCollection {
+    pub fn new() -> GuildCollection {
+        GuildCollection {
+            guilds: HashMap::new(),
+            direct_messages: ScheduleCollection::new(),
+            legacy: None,
+        }
+    }
+
+    /// Wraps the single, global collection of schedules used by older
+    /// versions of the bot, so that existing data is not lost.
+    pub fn from_legacy(schedule: ScheduleCollection) -> GuildCollection {
+        GuildCollection {
+            guilds: HashMap::new(),
+            direct_messages: ScheduleCollection::new(),
+            legacy: Some(schedule),
+        }
+    }
+
+    /// Reassembles a collection from its scopes, as they are kept in storage.
+    pub fn from_scopes(
+        guilds: HashMap<u64, ScheduleCollection>,
+        direct_messages: ScheduleCollection,
+        legacy: Option<ScheduleCollection>,
+    ) -> GuildCollection {
+        GuildCollection {
+            guilds,
+            direct_messages,
+            legacy,
+        }
+    }
+
+    /// Returns every guild which has been seen, along with the direct message scope (None).
+    pub fn scopes(&self) -> Vec<(Option<u64>, &ScheduleCollection)> {
+        self.guilds
+            .iter()
+            .map(|(id, schedule)| (Some(*id), schedule))
+            .chain(std::iter::once((None, &self.direct_messages)))
+            .collect()
+    }
+
+    /// Retrieves the schedules from before data was isolated by guild,
+    /// if they have not been moved into a guild yet.
+    pub fn legacy(&self) -> Option<&ScheduleCollection> {
+        self.legacy.as_ref()
+    }
+
+    /// Retrieves the schedules of a guild which has been seen,
+    /// or the direct message scope.
+    pub fn scope(&self, guild_id: Option<u64>) -> Option<&ScheduleCollection> {
+        match guild_id {
+            Some(id) => self.guilds.get(&id),
+            None => Some(&self.direct_messages),
+        }
+    }
+
+    /// Retrieves the schedules belonging to the guild the message was sent in,
+    /// or the direct message scope if it was not sent in a guild.
+    /// Guilds are created empty when they are first used.
+    pub fn scope_mut(&mut self, guild_id: Option<u64>) -> &mut ScheduleCollection {
+        match guild_id {
+            Some(id) => self.guilds.entry(id).or_default(),
+            None => &mut self.direct_messages,
+        }
+    }
+
+    /// Moves the schedules from before data was isolated by guild into a single guild,
+    /// which should be the one the bot was used in at the time.
+    /// Users who are already in the guild are kept as they are.
+    /// Returns how many users were moved, or an error if there is nothing to move.
+    pub fn move_legacy(&mut self, guild_id: u64) -> Result<usize, &'static str> {
+        let legacy = self
+            .legacy
+            .take()
+            .ok_or("There are no schedules from before data was kept per guild")?;
+        Ok(self.scope_mut(Some(guild_id)).merge_users(legacy))
+    }
+
+    /// Returns the reminders for events in every scope which are due at that moment,
+    /// marking them as sent.
+    pub fn due_reminders(&mut self, at: DateTime<Utc>) -> Vec<Reminder> {
+        self.guilds
+            .values_mut()
+            .chain(std::iter::once(&mut self.direct_messages))
+            .flat_map(|schedule| schedule.due_reminders(at))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day::Day;
+    use chrono::Utc;
+
+    #[test]
+    fn test_guild_isolation() {
+        let mut legacy = ScheduleCollection::new();
+        legacy.insert_user(1, "alice");
+        legacy.insert_user(2, "bob");
+        let mut guilds = GuildCollection::from_legacy(legacy);
+
+        let fst = guilds.scope_mut(Some(10));
+        fst.insert_user(2, "bob");
+        fst.mut_user(2).unwrap().set_time(Day::Mon, 5 * 60, true);
+        assert!(fst.user(1).is_none());
+        assert_eq!(
+            fst.available_at(Day::Mon, 5 * 60, 0, Utc::now()),
+            vec!["bob".to_string()]
+        );
+
+        let snd = guilds.scope_mut(Some(20));
+        assert!(snd.user(1).is_none());
+        assert!(snd.user(2).is_none());
+        assert!(snd.available_at(Day::Mon, 5 * 60, 0, Utc::now()).is_empty());
+
+        let direct = guilds.scope_mut(None);
+        assert!(direct.user(1).is_none());
+
+        // The old schedules are only moved into the chosen guild, once.
+        assert_eq!(guilds.move_legacy(10), Ok(1));
+        assert!(guilds.legacy().is_none());
+        let fst = guilds.scope_mut(Some(10));
+        assert!(fst.user(1).is_some());
+        assert!(fst
+            .user(2)
+            .unwrap()
+            .is_available(Day::Mon, 5 * 60, 0, Utc::now()));
+        assert!(guilds.scope_mut(Some(20)).user(1).is_none());
+        assert!(guilds.scope_mut(Some(30)).user(1).is_none());
+        assert!(guilds.move_legacy(20).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//===----------------------------------------------------------------------===//
+// lib.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+//! Schedules when people are available, and plans events around them.
+//!
+//! The scheduler does not depend on a chat platform, so it can be embedded in any bot.
+//! Queries are handled in three steps:
+//! - [`parse`] turns the text of a query into a [`ParamType`], which describes
+//!   the type of query, and the [`ParamVals`] passed to it.
+//! - [`process()`] applies the query to the [`ScheduleCollection`] of the guild
+//!   it was sent in, and renders the reply.
+//! - [`Bot`] does both for a message from any chat platform, keeping the
+//!   schedules of every guild and saving them to a [`Storage`] whenever they change.
+//!
+//! ```
+//! use scheduler_bot::{filter_query, parse_query, process, ScheduleCollection};
+//!
+//! let mut schedule = ScheduleCollection::new();
+//! schedule.register_user(1, "alice", None);
+//! let (p_type, vals) = parse_query(filter_query("?add mon from 18 to 20")).unwrap();
+//! process(&mut schedule, 1, 0, p_type, vals).unwrap();
+//!
+//! let (p_type, vals) = parse_query(filter_query("?available mon 18")).unwrap();
+//! let reply = process(&mut schedule, 1, 0, p_type, vals).unwrap().unwrap();
+//! assert!(reply.contains("alice"));
+//! ```
+//!
+//! The Discord bot is built on top of the library, behind the `discord` feature,
+//! and the SQLite storage it may use is behind the `sqlite` feature.
+//! Both are enabled by default. Library users who do not need them can disable
+//! default features, so that neither serenity nor SQLite is compiled.
+
+/// The chat-platform-neutral core, which handles messages and sends reminders.
+pub mod bot;
+/// Messages to and from chat platforms, and the adapter trait they implement.
+pub mod chat;
+/// Sources of the current time, which can be replaced in tests.
+pub mod clock;
+/// How the bot is used in each guild, eg. the prefix of queries.
+pub mod config;
+/// Days of the week.
+pub mod day;
+/// The Discord adapter, built on serenity.
+#[cfg(feature = "discord")]
+#[doc(hidden)]
+pub mod discord;
+/// Errors which are explained to the user, along with how to use the command.
+pub mod error;
+/// Planned sessions which users can respond to.
+pub mod event;
+/// The schedules of every guild, kept separately.
+pub mod guilds;
+/// Turning the text of a query into tokens.
+pub mod parse;
+/// Applying queries to schedules, and rendering the replies.
+pub mod process;
+/// Reminders which are sent before events.
+pub mod reminder;
+/// A front end which reads queries from any input, without a chat platform.
+#[doc(hidden)]
+pub mod repl;
+/// The schedules of every user in a guild.
+pub mod schedules;
+/// The versions of the data format, and the migrations between them.
+pub(crate) mod schema;
+/// Discord slash commands, and turning interactions into queries.
+/// Does not depend on serenity, so it can be used with any Discord library.
+#[doc(hidden)]
+pub mod slash;
+/// Keeping the data in an SQLite database.
+#[cfg(feature = "sqlite")]
+#[doc(hidden)]
+pub mod sqlite;
+/// Keeping the data between runs, and the JSON data file.
+pub mod storage;
+/// Timezone names and their offsets.
+pub(crate) mod timezone;
+/// The schedule of a single user.
+pub mod user;
+
+pub use bot::Bot;
+pub use chat::{Author, ChatAdapter, IncomingMessage, Reply};
+pub use clock::{Clock, SystemClock};
+pub use config::{Access, GuildConfig};
+pub use error::QueryError;
+pub use event::{Event, EventCollection, Rsvp};
+pub use guilds::GuildCollection;
+pub use parse::{filter_query, parse_query, parse_query_with_config, ParamType, ParamVals};
+pub use process::{process, process_with_clock, respond};
+pub use reminder::ReminderScheduler;
+pub use schedules::ScheduleCollection;
+pub use storage::{JsonStorage, Storage};
+pub use user::User;
@@ -6,119 +6,136 @@
 // Copyright (c) 2020 Philippe Nadon
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
-mod day;
-mod parse;
-mod process;
-mod schedules;
-mod user;
-
-use std::fs;
-
-use parse::{filter_query, parse_query};
-use schedules::ScheduleCollection;
+use scheduler_bot::repl::{self, Repl};
+#[cfg(feature = "sqlite")]
+use scheduler_bot::sqlite::SqliteStorage;
+use scheduler_bot::{Bot, GuildCollection, JsonStorage, Storage};
 
 use std::env;
-
-use serenity::{
-    model::{
-        channel::Message,
-        gateway::{Activity, Ready},
-    },
-    prelude::*,
-};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::Arc;
 
 static DATA_FNAME: &str = "./data.json";
+#[cfg(feature = "sqlite")]
+static DATABASE_FNAME: &str = "./data.db";
 
-/// Wrapper for persistent data.
-struct PersistentData;
-
-impl TypeMapKey for PersistentData {
-    type Value = ScheduleCollection;
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.iter().any(|arg| arg == "--import") {
+        import_data();
+    } else if let Some(position) = args.iter().position(|arg| arg == "--legacy-guild") {
+        move_legacy(args.get(position + 1));
+    } else if args.iter().any(|arg| arg == "--repl") {
+        run_repl(&args);
+    } else {
+        run_bot();
+    }
 }
 
-/// Observes and handles events.
-struct Handler;
+/// Opens the storage selected by the STORAGE environment variable,
+/// either "json" (the default) for a single data file, or "sqlite" for a database.
+/// Failures are logged rather than stopping the bot when saving, since the
+/// previously saved data is left untouched.
+fn open_storage() -> Arc<dyn Storage> {
+    match env::var("STORAGE").as_deref() {
+        #[cfg(feature = "sqlite")]
+        Ok("sqlite") => Arc::new(open_database()),
+        Ok("json") | Err(_) => Arc::new(JsonStorage::new(Path::new(DATA_FNAME))),
+        Ok(other) => panic!("Unknown storage \"{}\", expected json or sqlite.", other),
+    }
+}
 
-impl EventHandler for Handler {
-    /// Reads incoming messages and parses them if they begin with "?".
-    /// Messages are parsed into tokens, which are then processed accordingly.
-    fn message(&self, ctx: Context, msg: Message) {
-        if msg.content.starts_with('?') {
-            let id = *msg.author.id.as_u64();
-            let name = &msg.author.name;
+#[cfg(feature = "sqlite")]
+fn open_database() -> SqliteStorage {
+    SqliteStorage::open(Path::new(DATABASE_FNAME))
+        .unwrap_or_else(|why| panic!("Could not open database: {}", why))
+}
 
-            // Data safely retrieved from persistent context.
-            // Take note that Discord bots may be multi-threaded.
-            let mut data = ctx.data.write();
-            let schedule = data.get_mut::<PersistentData>().unwrap();
+/// Copies the data file into the database, so that the bot can switch to it.
+#[cfg(feature = "sqlite")]
+fn import_data() {
+    match open_database().import_json(Path::new(DATA_FNAME)) {
+        Ok(()) => println!("Imported {} into {}.", DATA_FNAME, DATABASE_FNAME),
+        Err(why) => println!("Could not import {}: {}", DATA_FNAME, why),
+    }
+}
 
-            // If the message contains valid tokens, processs them.
-            if let (Some(p_type), Some(vals)) = parse_query(filter_query(&msg.content)) {
-                // If the user is interacting with the bot for the first time,
-                // they must be registered first.
-                if schedule.get_id(name).is_none() {
-                    if !schedule.id_exists(id) {
-                        schedule.insert_user(id, name);
-                    }
-                    if let Err(why) = schedule.add_name_id(name, id) {
-                        println!("Error adding user: {:?}", why);
-                    }
-                }
+#[cfg(not(feature = "sqlite"))]
+fn import_data() {
+    eprintln!("Built without the sqlite feature, so there is no database to import into.");
+    std::process::exit(2);
+}
 
-                match process::process(schedule, name, p_type, vals) {
-                    Ok(res) => {
-                        if let Some(res_msg) = res {
-                            if let Err(why) = msg.channel_id.say(&ctx.http, res_msg) {
-                                println!("Error sending message: {:?}", why);
-                            }
-                        }
-                    }
-                    Err(why) => {
-                        println!("Error parsing message: {:?}", why);
-                    }
-                }
-            } else if let Err(why) = msg.channel_id.say(&ctx.http, "Failed to parse message") {
-                println!("Error sending message: {:?}", why);
-            }
-            fs::write(DATA_FNAME, serde_json::to_string(&schedule).unwrap())
-                .expect("failed to write file");
+/// Moves the schedules from before data was kept per guild into the guild
+/// the bot was used in at the time, so that no other guild can see them.
+fn move_legacy(guild: Option<&String>) {
+    let guild_id = match guild.and_then(|id| id.parse::<u64>().ok()) {
+        Some(guild_id) => guild_id,
+        None => {
+            eprintln!("Usage: scheduler_bot --legacy-guild <id>");
+            std::process::exit(2);
         }
+    };
+    let storage = open_storage();
+    let mut guilds = load_data(storage.as_ref());
+    match guilds.move_legacy(guild_id) {
+        Ok(count) => match storage.save(&guilds) {
+            Ok(()) => println!("Moved {} users into guild {}.", count, guild_id),
+            Err(why) => println!("Could not save the data: {}", why),
+        },
+        Err(why) => println!("{}.", why),
     }
+}
 
-    /// Executes when the bot first starts.
-    fn ready(&self, ctx: Context, ready: Ready) {
-        ctx.set_activity(Activity::playing("Type \"?help\" to get started!"));
-        println!("{} is connected!", ready.user.name);
+/// Reads queries from stdin and prints the replies, without connecting to Discord.
+/// The data is kept in a separate data file, so that the data of the bot is never touched.
+fn run_repl(args: &[String]) {
+    let mut repl = Repl::from_args(args).unwrap_or_else(|why| {
+        eprintln!("{}\n{}", why, repl::USAGE);
+        std::process::exit(2);
+    });
+    let storage = Arc::new(JsonStorage::new(repl.data()));
+    let bot = Bot::new(load_data(storage.as_ref()), storage);
+
+    let stdin = io::stdin();
+    let prompt = stdin.is_terminal();
+    if let Err(why) = repl::run(&mut repl, &bot, stdin.lock(), &mut io::stdout(), prompt) {
+        eprintln!("Error reading queries: {}", why);
     }
 }
 
-fn main() {
-    run_bot();
+/// Loads the saved data, refusing to continue if it could not be read.
+fn load_data(storage: &dyn Storage) -> GuildCollection {
+    let guilds = storage.load().unwrap_or_else(|why| {
+        panic!(
+            "Refusing to start, since the saved data could not be read:\n{}",
+            why
+        )
+    });
+    if guilds.legacy().is_some() {
+        eprintln!(
+            "Found schedules from before data was kept per guild, which no guild can see. \
+             Move them into the guild they were used in with --legacy-guild <id>."
+        );
+    }
+    guilds
 }
 
-/// Retrieve's the token as well as set the persistent data,
-/// before starting the bot.
+/// Retrieve's the token as well as load the persistent data,
+/// before connecting the bot to Discord.
+#[cfg(feature = "discord")]
 fn run_bot() {
-    let mut client = Client::new(
-        &env::var("DISCORD_TOKEN").expect("Could not find token."),
-        Handler,
-    )
-    .expect("Could not create client.");
-
-    {
-        let mut data = client.data.write();
-        if let Ok(serialized_schedule) = fs::read_to_string(DATA_FNAME) {
-            if let Ok(schedule) = serde_json::from_str(&serialized_schedule) {
-                data.insert::<PersistentData>(schedule);
-            } else {
-                println!("Error parsing data file. Creating new...");
-                data.insert::<PersistentData>(ScheduleCollection::new());
-            }
-        } else {
-            println!("Could not find data file. Creating new...");
-            data.insert::<PersistentData>(ScheduleCollection::new());
-        }
-    }
+    let token = env::var("DISCORD_TOKEN").expect("Could not find token.");
+    let storage = open_storage();
+    let bot = Bot::new(load_data(storage.as_ref()), storage);
+    scheduler_bot::discord::run(Arc::new(bot), &token);
+}
 
-    client.start().expect("Could not start client.");
+#[cfg(not(feature = "discord"))]
+fn run_bot() {
+    eprintln!(
+        "Built without the discord feature, only --repl, --import and --legacy-guild are available."
+    );
+    std::process::exit(2);
 }
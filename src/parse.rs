@@ -6,54 +6,232 @@
 // Copyright (c) 2020 Philippe Nadon
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
-use crate::day::Day;
+use crate::config::{GuildConfig, MAX_PREFIX_LEN};
+use crate::day::{Day, RelativeDate};
+use crate::error::*;
+use crate::event::Rsvp;
+use crate::timezone::find_timezone;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::slice::Iter;
+
+/// The built-in names for ranges of times, along with their start and end (in minutes).
+pub const PERIODS: [(&str, u32, u32); 5] = [
+    ("morning", 6 * 60, 12 * 60),
+    ("afternoon", 12 * 60, 18 * 60),
+    ("evening", 18 * 60, 24 * 60),
+    ("night", 0, 6 * 60),
+    ("allday", 0, 24 * 60),
+];
 
 /// Tokens representing the type of query.
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
 pub enum ParamType {
+    /// Sets the user's timezone.
     TimeZone,
+    /// Sets the user's display name.
     Name,
+    /// Adds times to the user's schedule.
     AddSchedule,
+    /// Removes times from the user's schedule.
     RemoveSchedule,
+    /// Shows a user's schedule.
     ViewSchedule,
+    /// Lists who is available at the given times.
     Available,
+    /// Marks the user as away at some times on a date.
+    Away,
+    /// Marks the user as available at some times on a date, in addition to their schedule.
+    Extra,
+    /// Finds the windows of some length when the most people are available.
+    Best,
+    /// Finds the times when the given users are all available.
+    Overlap,
+    /// Plans an event.
+    CreateEvent,
+    /// Lists the upcoming events.
+    ListEvents,
+    /// Shows an event, along with the responses.
+    ViewEvent,
+    /// Responds to an event.
+    Rsvp,
+    /// Cancels an event.
+    CancelEvent,
+    /// Changes when the reminder for an event is sent.
+    Remind,
+    /// Replies with a meme.
     Meme,
+    /// Explains how to use the bot, or a command.
     Help,
+    /// Shows how the bot is used in the guild.
+    ViewConfig,
+    /// Sets the prefix queries begin with in the guild.
+    Prefix,
+    /// Sets or removes another name for a command in the guild.
+    Alias,
+    /// Lists the built-in periods, and those defined in the guild.
+    ListPeriods,
+    /// Defines or removes a named range of times in the guild.
+    Period,
 }
 
 /// Tokens representing the values passed to the user's query.
+/// Times are in minutes since midnight, and time ranges exclude their end.
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub enum ParamVals {
+    /// Individual times.
     TimeCollection(Vec<u32>),
+    /// Individual days, eg. weekends.
     DayCollection(Vec<Day>),
+    /// The start and end of a range of times.
     TimeRange(u32, u32),
+    /// The first and last day of a range of days.
     DayRange(Day, Day),
+    /// A calendar date.
+    Date(NaiveDate),
+    /// A date relative to the day the query is sent, in the user's timezone.
+    RelativeDate(RelativeDate),
+    /// A length of time, in minutes.
+    Duration(u32),
+    /// Users, as they were referred to.
+    Users(Vec<String>),
+    /// A display name.
     Name(String),
+    /// A fixed offset from UTC, in minutes.
     TimeZone(i32),
+    /// The name of a timezone, which follows daylight saving time.
+    TimeZoneName(String),
+    /// The user whose schedule is shown.
     ViewId(String),
+    /// The title of an event.
+    Title(String),
+    /// The id of an event.
+    EventId(u32),
+    /// A response to an event.
+    Rsvp(Rsvp),
+    /// Minutes before an event to remind, if at all, and whether to send it directly.
+    Reminder(Option<u32>, bool),
+    /// The prefix queries begin with.
+    Prefix(String),
+    /// Another name for a command, and the command it runs, or None to remove it.
+    Alias(String, Option<String>),
+    /// The name of a period, and its range of times, or None to remove it.
+    Period(String, Option<(u32, u32)>),
+    /// Separates the clauses of a schedule query from the exceptions after it.
+    Except,
 }
 
 /// Transforms the raw text of the query into a cleaned list of params.
 /// Examples include splitting by spaces and commands, and lowercasing input.
+/// Text in quotes is kept as a single param, as it was written and along with
+/// the quotes, so that it is never read as anything but text, eg. a day.
+/// Symbols before the command, such as a prefix on its own, are dropped.
 pub fn filter_query(input: &str) -> Vec<String> {
     input
-        .split(|chr| chr == ' ' || chr == ',')
+        .split(['"', '“', '”'])
+        .enumerate()
+        .flat_map(|(idx, part)| {
+            if idx % 2 == 1 {
+                vec![part.trim()]
+                    .into_iter()
+                    .filter(|word| !word.is_empty())
+                    .map(|word| "\"".to_string() + word + "\"")
+                    .collect::<Vec<String>>()
+            } else if idx == 0 {
+                filter_words(part)
+                    .into_iter()
+                    .skip_while(|word| !word.chars().any(|chr| chr.is_alphanumeric()))
+                    .collect::<Vec<String>>()
+            } else {
+                filter_words(part)
+            }
+        })
+        .collect::<Vec<String>>()
+}
+
+/// Checks if the param was written in quotes.
+fn is_quoted(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('"') && word.ends_with('"')
+}
+
+/// Returns the text of a param, without the quotes it was written in, if any.
+fn unquote(word: &str) -> &str {
+    match is_quoted(word) {
+        true => &word[1..word.len() - 1],
+        false => word,
+    }
+}
+
+/// Splits unquoted text into cleaned, lowercase params.
+/// Words made only of symbols are kept as they are, eg. a prefix such as !.
+/// The @ of a mention is kept, so that it is never read as anything but a user.
+/// Dashes are read as hyphens, and ranges and times which were written
+/// with spaces are joined, eg. "7 pm - 11 pm" becomes "7pm-11pm".
+fn filter_words(input: &str) -> Vec<String> {
+    let words = input
+        .replace(['–', '—'], "-")
+        .split([' ', ','])
         .map(|word| {
+            if !word.chars().any(|chr| chr.is_alphanumeric()) {
+                return word.to_string();
+            }
             word.chars()
-                .filter(|chr| chr.is_ascii_alphanumeric() || chr == &'-')
+                .filter(|chr| {
+                    chr.is_ascii_alphanumeric() || ['-', '+', ':', '.', '/', '_', '@'].contains(chr)
+                })
                 .collect::<String>()
+                .trim_end_matches(['.', ':'])
+                .to_string()
         })
         .filter(|word| !word.is_empty())
         .map(|word| word.to_lowercase())
-        .collect::<Vec<String>>()
+        .collect::<Vec<String>>();
+    join_words(words)
+}
+
+/// Joins am or pm to the time before it, and a hyphen to the words around it.
+fn join_words(words: Vec<String>) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    let mut hyphen = false;
+    for word in words {
+        let after_digit = res
+            .last()
+            .is_some_and(|last| last.ends_with(|chr: char| chr.is_ascii_digit()));
+        if (word == "am" || word == "pm") && after_digit {
+            *res.last_mut().unwrap() += &word;
+        } else if word == "-" && !res.is_empty() && !hyphen {
+            hyphen = true;
+        } else if hyphen {
+            *res.last_mut().unwrap() += &("-".to_string() + &word);
+            hyphen = false;
+        } else {
+            res.push(word);
+        }
+    }
+    if hyphen {
+        res.push("-".to_string());
+    }
+    res
+}
+
+/// Parses the list of params into tokens representing their value,
+/// with the default config, so only the built-in periods are known.
+pub fn parse_query(params: Vec<String>) -> Result<(ParamType, Vec<ParamVals>), QueryError> {
+    parse_query_with_config(params, &GuildConfig::default())
 }
 
 /// Parses the list of params into tokens representing their value.
 /// The function mostly serves as a router to sub-functions which handle
 /// each individual type of query.
-pub fn parse_query(params: Vec<String>) -> (Option<ParamType>, Option<Vec<ParamVals>>) {
+/// The periods defined in the guild's config are read wherever times are expected.
+pub fn parse_query_with_config(
+    params: Vec<String>,
+    config: &GuildConfig,
+) -> Result<(ParamType, Vec<ParamVals>), QueryError> {
+    let periods = config.periods();
     if params.is_empty() {
-        return (None, None);
+        return Err(QueryError::NoCommand);
     }
     let param_type_str = params.first().unwrap().as_str();
     let param_vals_str = params[1..]
@@ -62,150 +240,842 @@ pub fn parse_query(params: Vec<String>) -> (Option<ParamType>, Option<Vec<ParamV
         .collect::<Vec<&str>>()
         .to_vec();
 
-    if param_type_str.starts_with("add") {
-        (Some(ParamType::AddSchedule), parse_schedule(param_vals_str))
+    let (p_type, vals) = if param_type_str.starts_with("add") {
+        (
+            ParamType::AddSchedule,
+            parse_schedule(param_vals_str, periods),
+        )
     } else if param_type_str.starts_with("remove") {
         (
-            Some(ParamType::RemoveSchedule),
-            parse_schedule(param_vals_str),
+            ParamType::RemoveSchedule,
+            parse_schedule(param_vals_str, periods),
         )
     } else if param_type_str.starts_with("name") {
-        (Some(ParamType::Name), parse_name(param_vals_str))
+        (ParamType::Name, parse_name(param_vals_str))
     } else if param_type_str.starts_with("timezone") {
-        (Some(ParamType::TimeZone), parse_timezone(param_vals_str))
+        (ParamType::TimeZone, parse_timezone(param_vals_str))
     } else if param_type_str.starts_with("view") {
+        (ParamType::ViewSchedule, parse_schedule_id(param_vals_str))
+    } else if param_type_str.starts_with("available") {
         (
-            Some(ParamType::ViewSchedule),
-            parse_schedule_id(param_vals_str),
+            ParamType::Available,
+            parse_available(param_vals_str, periods),
         )
-    } else if param_type_str.starts_with("available") {
-        (Some(ParamType::Available), parse_schedule(param_vals_str))
+    } else if param_type_str.starts_with("away") {
+        (
+            ParamType::Away,
+            parse_date_override(param_vals_str, periods),
+        )
+    } else if param_type_str.starts_with("extra") {
+        (
+            ParamType::Extra,
+            parse_date_override(param_vals_str, periods),
+        )
+    } else if param_type_str.starts_with("best") {
+        (ParamType::Best, parse_best(param_vals_str))
+    } else if param_type_str.starts_with("overlap") {
+        (ParamType::Overlap, parse_overlap(param_vals_str))
+    } else if param_type_str.starts_with("event") {
+        return parse_event(param_vals_str);
     } else if param_type_str.starts_with("showtime") {
-        (Some(ParamType::Meme), Some(vec![]))
+        (ParamType::Meme, Ok(vec![]))
     } else if param_type_str.starts_with("help") {
-        (Some(ParamType::Help), Some(vec![]))
+        (ParamType::Help, Ok(vec![]))
+    } else if param_type_str.starts_with("config") {
+        return parse_config(param_vals_str);
+    } else if param_type_str.starts_with("period") {
+        return parse_period_query(param_vals_str, config);
     } else {
-        (None, None)
+        return Err(QueryError::UnknownCommand(param_type_str.to_string()));
+    };
+    match vals {
+        Ok(vals) => Ok((p_type, vals)),
+        Err(why) => Err(QueryError::Syntax(p_type, why)),
     }
 }
 
 /// Parses the value of the inputted name.
-fn parse_name(params: Vec<&str>) -> Option<Vec<ParamVals>> {
+fn parse_name(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
     if !params.is_empty() {
-        Some(vec![ParamVals::Name(params.concat())])
+        Ok(vec![ParamVals::Name(
+            params
+                .concat()
+                .chars()
+                .filter(|chr| chr.is_ascii_alphanumeric() || chr == &'-')
+                .collect(),
+        )])
     } else {
-        Some(vec![])
+        Ok(vec![])
     }
 }
 
-/// Parses the value of the user id.
-fn parse_schedule_id(params: Vec<&str>) -> Option<Vec<ParamVals>> {
-    if params.len() > 4 {
-        Some(vec![ParamVals::ViewId(
-            params[..(params.len() - 4)].concat() + "#" + &params[(params.len() - 4)..].concat(),
+/// Parses the user whose schedule is viewed, which is either a mention (their id)
+/// or a name, which may contain spaces, eg. @alice, alice or captain al.
+fn parse_schedule_id(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    if !params.is_empty() {
+        Ok(vec![ParamVals::ViewId(
+            params
+                .iter()
+                .map(|word| unquote(word.trim_start_matches('@')))
+                .collect::<Vec<&str>>()
+                .join(" "),
         )])
     } else {
-        Some(vec![])
+        Ok(vec![])
     }
 }
 
-/// Parses the value of the inputted timezone.
-fn parse_timezone(params: Vec<&str>) -> Option<Vec<ParamVals>> {
-    if !params.is_empty() {
-        if let Ok(num) = params.first().unwrap().parse::<f64>() {
-            let time_offset: i32 = get_largest_digit(num);
-            if time_offset > -24 && time_offset < 24 {
-                return Some(vec![ParamVals::TimeZone(time_offset)]);
-            }
+/// Parses the value of the inputted timezone, either as an offset from UTC
+/// in minutes, or as the name of a timezone, eg. America/Vancouver or PST.
+fn parse_timezone(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let word = match params_iter.next() {
+        Some(word) => *word,
+        None => return Ok(vec![]),
+    };
+    let res = if let Some(time_offset) = parse_utc_offset(word) {
+        if time_offset > -24 * 60 && time_offset < 24 * 60 {
+            ParamVals::TimeZone(time_offset)
+        } else {
+            return Err(invalid(word, EXPECTED_TIMEZONE));
         }
+    } else if let Some(tz) = find_timezone(word) {
+        ParamVals::TimeZoneName(tz.name().to_string())
     } else {
-        return Some(vec![]);
-    }
-    None
+        return Err(invalid(word, EXPECTED_TIMEZONE));
+    };
+    expect_end(&mut params_iter)?;
+    Ok(vec![res])
 }
 
-/// Retrieves the largest digit in an int.
-fn get_largest_digit(num: f64) -> i32 {
-    num as i32 / 10i32.pow(num.abs().log10().trunc() as u32)
+/// Parses an offset from UTC into minutes.
+/// Accepts hours (-7), hours and minutes (+5:30, 530, -0330),
+/// as well as fractions of hours (5.5, 5.75).
+fn parse_utc_offset(word: &str) -> Option<i32> {
+    let (sign, digits) = match word.chars().next()? {
+        '-' => (-1, &word[1..]),
+        '+' => (1, &word[1..]),
+        _ => (1, word),
+    };
+    if !digits.starts_with(|chr: char| chr.is_ascii_digit()) {
+        return None;
+    }
+
+    let minutes = if let Some((hours, minutes)) = digits.split_once(':') {
+        let minutes = minutes.parse::<u32>().ok()?;
+        if minutes >= 60 {
+            return None;
+        }
+        hours.parse::<u32>().ok()? * 60 + minutes
+    } else if digits.contains('.') {
+        (digits.parse::<f64>().ok()? * 60.0).round() as u32
+    } else {
+        let num = digits.parse::<u32>().ok()?;
+        // More than two digits means the minutes were included, eg. 530.
+        if digits.len() > 2 {
+            if num % 100 >= 60 {
+                return None;
+            }
+            (num / 100) * 60 + num % 100
+        } else {
+            num * 60
+        }
+    };
+    Some(sign * minutes as i32)
 }
 
 /// Parses the values corresponding to a query related to the schedule itself.
 /// Handles various cases such as a day and/or time range,
 /// as well as multiple specific days and/or times.
-fn parse_schedule(params: Vec<&str>) -> Option<Vec<ParamVals>> {
+/// Several clauses of days followed by times may be given, eg. mon-thu 19-23, fri 18-22,
+/// and times without days reuse the days of the clause before them.
+/// Times which end before they start continue past midnight, eg. fri 18-2.
+/// Clauses after "except" are left out of the ones before them, eg. weekdays 18-23 except wed,
+/// and cover the whole day unless times are given.
+fn parse_schedule(
+    params: Vec<&str>,
+    periods: &BTreeMap<String, (u32, u32)>,
+) -> Result<Vec<ParamVals>, TokenError> {
     let mut params_iter = params.iter().peekable();
     let mut res: Vec<ParamVals> = vec![];
-    let mut param = params_iter.next();
+    let mut except = false;
+    let param = next_param(&mut params_iter, EXPECTED_DAYS_OR_TIMES)?;
+    let mut clause = parse_clause(
+        param,
+        &mut params_iter,
+        ParamVals::DayRange(Day::Sun, Day::Sat),
+        periods,
+    )?;
+    loop {
+        if clause.len() == 1 {
+            if except {
+                clause.push(ParamVals::TimeRange(0, 24 * 60));
+            } else if !res.is_empty() || params_iter.peek().is_some() {
+                return Err(TokenError::Missing {
+                    expected: EXPECTED_TIMES,
+                });
+            }
+        }
+        let days = clause[0].clone();
+        res.append(&mut clause);
+        let param = match params_iter.next() {
+            Some(param) => param,
+            None => return Ok(res),
+        };
+        if !except && param.starts_with("except") {
+            except = true;
+            res.push(ParamVals::Except);
+            let param = next_param(&mut params_iter, EXPECTED_DAYS_OR_TIMES)?;
+            clause = parse_clause(param, &mut params_iter, days, periods)?;
+        } else if is_day_spec(param) || is_time_spec(param, periods) {
+            clause = parse_clause(param, &mut params_iter, days, periods)?;
+        } else {
+            return Err(TokenError::Unexpected(param.to_string()));
+        }
+    }
+}
 
-    param?;
+/// Parses a single clause of a schedule query, which is the days followed by the times.
+/// If no days are given, the clause applies to the given days instead.
+/// The times are left out if the clause is followed by the next one, or nothing.
+fn parse_clause(
+    param: &str,
+    params_iter: &mut Peekable<Iter<&str>>,
+    days: ParamVals,
+    periods: &BTreeMap<String, (u32, u32)>,
+) -> Result<Vec<ParamVals>, TokenError> {
+    match parse_days(param, params_iter)? {
+        Some(days) => match params_iter.peek() {
+            Some(next) if !next.starts_with("except") && !starts_days(next) => {
+                let param = params_iter.next().unwrap();
+                Ok(vec![days, parse_times(param, params_iter, periods)?])
+            }
+            _ => Ok(vec![days]),
+        },
+        None => {
+            if !is_time_spec(param, periods) {
+                return Err(invalid(param, EXPECTED_DAYS_OR_TIMES));
+            }
+            Ok(vec![days, parse_times(param, params_iter, periods)?])
+        }
+    }
+}
 
-    if param.unwrap().starts_with("weekday") {
-        res.push(ParamVals::DayRange(Day::Mon, Day::Fri));
-        param = params_iter.next();
-    } else if param.unwrap().starts_with("weekend") {
-        res.push(ParamVals::DayRange(Day::Sat, Day::Sun));
-        param = params_iter.next();
-    } else if param.unwrap().starts_with("from")
+/// Parses the days of a query, starting with the current param.
+/// Returns None if the param does not start any days, in which case
+/// the query applies to the whole week.
+fn parse_days(
+    param: &str,
+    params_iter: &mut Peekable<Iter<&str>>,
+) -> Result<Option<ParamVals>, TokenError> {
+    if let Some((fst_day, snd_day)) = parse_day_range(param) {
+        Ok(Some(ParamVals::DayRange(fst_day, snd_day)))
+    } else if param.starts_with("weekday") {
+        Ok(Some(ParamVals::DayRange(Day::Mon, Day::Fri)))
+    } else if param.starts_with("weekend") {
+        Ok(Some(ParamVals::DayRange(Day::Sat, Day::Sun)))
+    } else if param.starts_with("from")
         && params_iter.peek().is_some()
         && parse_day(params_iter.peek().unwrap()).is_ok()
     {
-        match (params_iter.next(), params_iter.next(), params_iter.next()) {
-            (Some(fst_input), Some(&"to"), Some(snd_input)) => {
-                if let Ok(fst_day) = parse_day(fst_input) {
-                    if let Ok(snd_day) = parse_day(snd_input) {
-                        res.push(ParamVals::DayRange(fst_day, snd_day))
-                    } else {
-                        return None;
-                    }
-                } else {
-                    return None;
-                }
-            }
-            (_, _, _) => return None,
-        }
-        param = params_iter.next();
-    } else if let Ok(fst_day) = parse_day(param.unwrap()) {
+        let fst_day = parse_day(params_iter.next().unwrap()).unwrap();
+        expect_param(params_iter, "to", EXPECTED_TO)?;
+        let snd_input = next_param(params_iter, EXPECTED_DAY)?;
+        let snd_day = parse_day(snd_input).map_err(|_| invalid(snd_input, EXPECTED_DAY))?;
+        Ok(Some(ParamVals::DayRange(fst_day, snd_day)))
+    } else if let Ok(fst_day) = parse_day(param) {
         let mut days = vec![fst_day];
-        while params_iter.peek().is_some() && parse_day(params_iter.peek().unwrap()).is_ok() {
+        while params_iter.peek().is_some()
+            && parse_day(params_iter.peek().unwrap()).is_ok()
+            && !params_iter.peek().unwrap().contains('-')
+        {
             days.push(parse_day(params_iter.next().unwrap()).unwrap());
         }
-        res.push(ParamVals::DayCollection(days));
-        param = params_iter.next();
+        Ok(Some(ParamVals::DayCollection(days)))
     } else {
-        res.push(ParamVals::DayRange(Day::Sun, Day::Sat));
-    }
-    if param.is_none() {
-        return Some(res);
-    }
-    if param.unwrap().starts_with("from") {
-        match (params_iter.next(), params_iter.next(), params_iter.next()) {
-            (Some(fst_input), Some(&"to"), Some(snd_input)) => {
-                if let Ok(fst_num) = fst_input.parse::<u32>() {
-                    if let Ok(snd_num) = snd_input.parse::<u32>() {
-                        if fst_num < 24 && snd_num < 24 {
-                            res.push(ParamVals::TimeRange(fst_num, snd_num))
-                        } else {
-                            return None;
-                        }
-                    } else {
-                        return None;
-                    }
-                } else {
-                    return None;
+        Ok(None)
+    }
+}
+
+/// Parses the values of a query about who is available,
+/// which is either about a day of the week as in parse_schedule,
+/// or a date, optionally followed by the times.
+fn parse_available(
+    params: Vec<&str>,
+    periods: &BTreeMap<String, (u32, u32)>,
+) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let param = next_param(&mut params_iter, EXPECTED_DAYS_OR_TIMES)?;
+    let res = match parse_date_spec(param, &mut params_iter)? {
+        Some(date) => match params_iter.next() {
+            Some(param) => vec![date, parse_times(param, &mut params_iter, periods)?],
+            None => vec![date],
+        },
+        None => parse_clause(
+            param,
+            &mut params_iter,
+            ParamVals::DayRange(Day::Sun, Day::Sat),
+            periods,
+        )?,
+    };
+    expect_end(&mut params_iter)?;
+    Ok(res)
+}
+
+/// Parses the values of a query which overrides the schedule on a date,
+/// eg. 2026-11-03 from 18 to 23.
+/// If no times are given, the override applies to the whole date,
+/// or from the evening on if it is tonight.
+fn parse_date_override(
+    params: Vec<&str>,
+    periods: &BTreeMap<String, (u32, u32)>,
+) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let word = next_param(&mut params_iter, EXPECTED_DATE)?;
+    let date =
+        parse_date_spec(word, &mut params_iter)?.ok_or_else(|| invalid(word, EXPECTED_DATE))?;
+    let res = match params_iter.next() {
+        Some(param) => vec![date, parse_times(param, &mut params_iter, periods)?],
+        None if date == ParamVals::RelativeDate(RelativeDate::Tonight) => {
+            let (start_time, end_time) = parse_period("evening", &BTreeMap::new()).unwrap();
+            vec![date, ParamVals::TimeRange(start_time, end_time)]
+        }
+        None => vec![date, ParamVals::TimeRange(0, 24 * 60)],
+    };
+    expect_end(&mut params_iter)?;
+    Ok(res)
+}
+
+/// Parses the values of a query for the best times to meet,
+/// which is a duration optionally followed by the days, eg. 3h weekend.
+fn parse_best(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let word = next_param(&mut params_iter, EXPECTED_DURATION)?;
+    let duration = parse_duration(word).ok_or_else(|| invalid(word, EXPECTED_DURATION))?;
+    Ok(vec![
+        ParamVals::Duration(duration),
+        parse_optional_days(&mut params_iter)?,
+    ])
+}
+
+/// Parses the values of a query for the common free time of some users,
+/// which is a list of users (mentions or names), optionally followed by the days.
+/// The days are read from the end of the query, after at least one user,
+/// so that users named like days are still read as users, eg. sunny.
+fn parse_overlap(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    if params.is_empty() {
+        return Err(TokenError::Missing {
+            expected: EXPECTED_USER,
+        });
+    }
+    let users_len = (1..params.len())
+        .find(|users_len| parse_optional_days(&mut params[*users_len..].iter().peekable()).is_ok())
+        .unwrap_or(params.len());
+    let users = ParamVals::Users(
+        params[..users_len]
+            .iter()
+            .map(|word| unquote(word.trim_start_matches('@')).to_string())
+            .collect(),
+    );
+    let mut params_iter = params[users_len..].iter().peekable();
+    Ok(vec![users, parse_optional_days(&mut params_iter)?])
+}
+
+/// Parses the days at the end of a query, which default to the whole week.
+/// Times are not allowed after the days.
+fn parse_optional_days(params_iter: &mut Peekable<Iter<&str>>) -> Result<ParamVals, TokenError> {
+    let days = match params_iter.next() {
+        Some(param) => {
+            parse_days(param, params_iter)?.ok_or_else(|| invalid(param, EXPECTED_DAY))?
+        }
+        None => ParamVals::DayRange(Day::Sun, Day::Sat),
+    };
+    expect_end(params_iter)?;
+    Ok(days)
+}
+
+/// Parses a query about events, which starts with the action to take.
+/// Creating an event takes a title, a day or date, a time and an optional duration,
+/// eg. "Raid night" fri 20 3h, and responding takes the event id and the response.
+fn parse_event(params: Vec<&str>) -> Result<(ParamType, Vec<ParamVals>), QueryError> {
+    let action = match params.first() {
+        Some(action) => *action,
+        None => return Ok((ParamType::ListEvents, vec![])),
+    };
+    let params = params[1..].to_vec();
+
+    let (p_type, vals) = if action.starts_with("create") {
+        (ParamType::CreateEvent, parse_create_event(params))
+    } else if action.starts_with("list") {
+        (ParamType::ListEvents, parse_end(params))
+    } else if action.starts_with("view") {
+        (ParamType::ViewEvent, parse_event_id(params))
+    } else if action.starts_with("rsvp") {
+        (ParamType::Rsvp, parse_rsvp(params))
+    } else if action.starts_with("cancel") {
+        (ParamType::CancelEvent, parse_event_id(params))
+    } else if action.starts_with("remind") {
+        (ParamType::Remind, parse_reminder(params))
+    } else {
+        return Err(QueryError::UnknownCommand("event ".to_string() + action));
+    };
+    vals.map(|vals| (p_type, vals))
+        .map_err(|why| QueryError::Syntax(p_type, why))
+}
+
+/// Parses a config query, which has an action, eg. config prefix !.
+/// The config is shown if there is no action.
+fn parse_config(params: Vec<&str>) -> Result<(ParamType, Vec<ParamVals>), QueryError> {
+    let action = match params.first() {
+        Some(action) => *action,
+        None => return Ok((ParamType::ViewConfig, vec![])),
+    };
+    let params = params[1..].to_vec();
+
+    let (p_type, vals) = if action.starts_with("prefix") {
+        (ParamType::Prefix, parse_prefix(params))
+    } else if action.starts_with("alias") {
+        (ParamType::Alias, parse_alias(params))
+    } else {
+        return Err(QueryError::UnknownCommand("config ".to_string() + action));
+    };
+    vals.map(|vals| (p_type, vals))
+        .map_err(|why| QueryError::Syntax(p_type, why))
+}
+
+/// Parses a new prefix, which must be a few symbols,
+/// so that ordinary messages are never mistaken for queries.
+fn parse_prefix(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let word = unquote(next_param(&mut params_iter, EXPECTED_PREFIX)?);
+    if word.chars().count() > MAX_PREFIX_LEN || !word.chars().all(|chr| chr.is_ascii_punctuation())
+    {
+        return Err(invalid(word, EXPECTED_PREFIX));
+    }
+    expect_end(&mut params_iter)?;
+    Ok(vec![ParamVals::Prefix(word.to_string())])
+}
+
+/// Checks if the words begin a command, eg. available or event list.
+fn is_command(words: &[&str]) -> bool {
+    !matches!(
+        parse_query(words.iter().map(|word| word.to_string()).collect()),
+        Err(QueryError::UnknownCommand(_))
+    )
+}
+
+/// Parses an alias, followed by the command it runs, if any.
+/// The alias cannot be a command itself, which it would hide.
+fn parse_alias(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let alias = unquote(next_param(&mut params_iter, EXPECTED_ALIAS)?);
+    if is_command(&[alias]) {
+        return Err(invalid(alias, EXPECTED_ALIAS));
+    }
+
+    let command = params_iter.copied().collect::<Vec<&str>>();
+    if command.is_empty() {
+        return Ok(vec![ParamVals::Alias(alias.to_string(), None)]);
+    }
+    if !is_command(&command) {
+        return Err(invalid(command[0], EXPECTED_COMMAND));
+    }
+    Ok(vec![ParamVals::Alias(
+        alias.to_string(),
+        Some(command.join(" ")),
+    )])
+}
+
+/// Parses a query about periods, which either defines or removes one,
+/// or lists them if there is no action.
+fn parse_period_query(
+    params: Vec<&str>,
+    config: &GuildConfig,
+) -> Result<(ParamType, Vec<ParamVals>), QueryError> {
+    let action = match params.first() {
+        Some(action) => *action,
+        None => return Ok((ParamType::ListPeriods, vec![])),
+    };
+    let mut params_iter = params[1..].iter().peekable();
+    let vals = if action.starts_with("define") {
+        parse_period_name(&mut params_iter, config).and_then(|name| {
+            let word = next_param(&mut params_iter, EXPECTED_PERIOD)?;
+            match parse_times(word, &mut params_iter, config.periods())? {
+                ParamVals::TimeRange(start_time, end_time) => {
+                    Ok(vec![ParamVals::Period(name, Some((start_time, end_time)))])
                 }
+                _ => Err(invalid(word, EXPECTED_PERIOD)),
             }
-            (_, _, _) => return None,
+        })
+    } else if action.starts_with("remove") {
+        parse_period_name(&mut params_iter, config).map(|name| vec![ParamVals::Period(name, None)])
+    } else {
+        return Err(QueryError::UnknownCommand("period ".to_string() + action));
+    };
+    vals.and_then(|vals| expect_end(&mut params_iter).map(|_| vals))
+        .map(|vals| (ParamType::Period, vals))
+        .map_err(|why| QueryError::Syntax(ParamType::Period, why))
+}
+
+/// Parses the name of a period defined in the guild, which is a single word
+/// that cannot be read as days, times, dates, commands or aliases, which it would hide.
+/// Periods already defined in the guild may be redefined.
+fn parse_period_name(
+    params_iter: &mut Peekable<Iter<&str>>,
+    config: &GuildConfig,
+) -> Result<String, TokenError> {
+    let name = next_param(params_iter, EXPECTED_PERIOD_NAME)?;
+    if !name.chars().all(|chr| chr.is_ascii_alphabetic())
+        || is_day_spec(name)
+        || is_time_spec(name, &BTreeMap::new())
+        || is_command(&[name])
+        || config.aliases().contains_key(name)
+        || [
+            "today", "tonight", "tomorrow", "next", "this", "to", "except",
+        ]
+        .contains(&name)
+    {
+        return Err(invalid(name, EXPECTED_PERIOD_NAME));
+    }
+    Ok(name.to_string())
+}
+
+/// Parses the values of a new event.
+/// The title is the param in quotes, if it is first,
+/// or otherwise every param before the day or date.
+fn parse_create_event(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let title_len = match params.first() {
+        Some(word) if is_quoted(word) => 1,
+        _ => params
+            .iter()
+            .position(|word| parse_date(word).is_some() || parse_day(word).is_ok())
+            .ok_or(TokenError::Missing {
+                expected: EXPECTED_DAY,
+            })?,
+    };
+    if title_len == 0 {
+        return Err(invalid(params[0], EXPECTED_TITLE));
+    }
+    let title = params[..title_len]
+        .iter()
+        .map(|word| unquote(word))
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let mut res = vec![ParamVals::Title(title)];
+    let mut params_iter = params[title_len..].iter().peekable();
+
+    let when = next_param(&mut params_iter, EXPECTED_DAY)?;
+    res.push(match parse_date(when) {
+        Some(date) => ParamVals::Date(date),
+        None => ParamVals::DayCollection(vec![
+            parse_day(when).map_err(|_| invalid(when, EXPECTED_DAY))?
+        ]),
+    });
+    let word = next_param(&mut params_iter, EXPECTED_TIME)?;
+    res.push(ParamVals::TimeCollection(vec![
+        parse_time(word).ok_or_else(|| invalid(word, EXPECTED_TIME))?
+    ]));
+    res.push(ParamVals::Duration(match params_iter.next() {
+        Some(word) => parse_duration(word).ok_or_else(|| invalid(word, EXPECTED_DURATION))?,
+        None => 60,
+    }));
+    expect_end(&mut params_iter)?;
+    Ok(res)
+}
+
+/// Parses the values of a response to an event, eg. 3 yes.
+fn parse_rsvp(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let mut res = vec![next_event_id(&mut params_iter)?];
+    let word = next_param(&mut params_iter, EXPECTED_RSVP)?;
+    res.push(ParamVals::Rsvp(match word {
+        "yes" | "y" => Rsvp::Yes,
+        "maybe" | "m" => Rsvp::Maybe,
+        "no" | "n" => Rsvp::No,
+        _ => return Err(invalid(word, EXPECTED_RSVP)),
+    }));
+    expect_end(&mut params_iter)?;
+    Ok(res)
+}
+
+/// Parses the values of an event's reminder, which is the event id followed by
+/// how long before the event to send it, and whether to also send it directly,
+/// eg. 3 30 dm. A number on its own is in minutes, and "off" removes the reminder.
+fn parse_reminder(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let mut res = vec![next_event_id(&mut params_iter)?];
+    let remind_before = match next_param(&mut params_iter, EXPECTED_REMINDER)? {
+        "off" | "none" => None,
+        word => Some(
+            word.parse::<u32>()
+                .ok()
+                .or_else(|| parse_duration(word))
+                .ok_or_else(|| invalid(word, EXPECTED_REMINDER))?,
+        ),
+    };
+    let direct = match params_iter.next() {
+        None => false,
+        Some(word) if word.starts_with("dm") => remind_before.is_some(),
+        Some(word) => return Err(invalid(word, EXPECTED_DM)),
+    };
+    expect_end(&mut params_iter)?;
+    res.push(ParamVals::Reminder(remind_before, direct));
+    Ok(res)
+}
+
+/// Parses the value of the event id, eg. 3 or #3.
+fn parse_event_id(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    let mut params_iter = params.iter().peekable();
+    let res = vec![next_event_id(&mut params_iter)?];
+    expect_end(&mut params_iter)?;
+    Ok(res)
+}
+
+/// Parses the next param as an event id.
+fn next_event_id(params_iter: &mut Peekable<Iter<&str>>) -> Result<ParamVals, TokenError> {
+    let word = next_param(params_iter, EXPECTED_EVENT_ID)?;
+    word.parse::<u32>()
+        .map(ParamVals::EventId)
+        .map_err(|_| invalid(word, EXPECTED_EVENT_ID))
+}
+
+/// Parses a query which takes no values.
+fn parse_end(params: Vec<&str>) -> Result<Vec<ParamVals>, TokenError> {
+    expect_end(&mut params.iter().peekable())?;
+    Ok(vec![])
+}
+
+/// Takes the next param, which the query requires.
+fn next_param<'a>(
+    params_iter: &mut Peekable<Iter<&'a str>>,
+    expected: &'static str,
+) -> Result<&'a str, TokenError> {
+    params_iter
+        .next()
+        .copied()
+        .ok_or(TokenError::Missing { expected })
+}
+
+/// Takes the next param, which must be exactly the word, eg. "to".
+fn expect_param(
+    params_iter: &mut Peekable<Iter<&str>>,
+    word: &str,
+    expected: &'static str,
+) -> Result<(), TokenError> {
+    match next_param(params_iter, expected)? {
+        param if param == word => Ok(()),
+        param => Err(invalid(param, expected)),
+    }
+}
+
+/// Checks that every param of the query was used.
+fn expect_end(params_iter: &mut Peekable<Iter<&str>>) -> Result<(), TokenError> {
+    match params_iter.next() {
+        Some(param) => Err(TokenError::Unexpected(param.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Describes a param which is not what was expected.
+fn invalid(token: &str, expected: &'static str) -> TokenError {
+    TokenError::Invalid {
+        token: token.to_string(),
+        expected,
+    }
+}
+
+/// Checks if the word starts the days of a schedule query.
+fn is_day_spec(word: &str) -> bool {
+    word.starts_with("weekday")
+        || word.starts_with("weekend")
+        || word == "from"
+        || parse_day(word).is_ok()
+}
+
+/// Checks if the word starts the days of the next clause of a schedule query,
+/// rather than the times of the current one.
+fn starts_days(word: &str) -> bool {
+    word != "from" && is_day_spec(word)
+}
+
+/// Checks if the word starts the times of a schedule query,
+/// including the periods defined in the guild.
+fn is_time_spec(word: &str, periods: &BTreeMap<String, (u32, u32)>) -> bool {
+    word.starts_with("from")
+        || parse_time(word).is_some()
+        || parse_time_range(word).is_some()
+        || parse_period(word, periods).is_some()
+}
+
+/// Parses the name of a period into its range of times, either a built-in one,
+/// eg. evening, or one of those defined in the guild.
+fn parse_period(word: &str, periods: &BTreeMap<String, (u32, u32)>) -> Option<(u32, u32)> {
+    PERIODS
+        .iter()
+        .find(|(name, _, _)| *name == word)
+        .map(|(_, start_time, end_time)| (*start_time, *end_time))
+        .or_else(|| periods.get(word).copied())
+}
+
+/// Parses a hyphenated range of days, eg. mon-thu.
+fn parse_day_range(word: &str) -> Option<(Day, Day)> {
+    let (fst, snd) = word.split_once('-')?;
+    Some((parse_day(fst).ok()?, parse_day(snd).ok()?))
+}
+
+/// Parses the value of the inputted duration into minutes,
+/// eg. 3h, 90m, 1h30m or 1.5h. A number on its own is in hours.
+fn parse_duration(word: &str) -> Option<u32> {
+    let minutes = if let Some((hours, minutes)) = word.split_once('h') {
+        let hours = (hours.parse::<f64>().ok()? * 60.0).round() as u32;
+        match minutes.trim_end_matches('m') {
+            "" => hours,
+            minutes => hours + minutes.parse::<u32>().ok()?,
         }
-    } else if let Ok(fst_time) = param.unwrap().parse::<u32>() {
+    } else if let Some(minutes) = word.strip_suffix('m') {
+        minutes.parse::<u32>().ok()?
+    } else {
+        (word.parse::<f64>().ok()? * 60.0).round() as u32
+    };
+    if minutes > 0 && minutes <= 7 * 24 * 60 {
+        Some(minutes)
+    } else {
+        None
+    }
+}
+
+/// Parses the times of a query, starting with the current param,
+/// either as a range (from <time> to <time>), a period, or as a collection of times.
+fn parse_times(
+    param: &str,
+    params_iter: &mut Peekable<Iter<&str>>,
+    periods: &BTreeMap<String, (u32, u32)>,
+) -> Result<ParamVals, TokenError> {
+    if param.starts_with("from") {
+        let fst_input = next_param(params_iter, EXPECTED_TIME)?;
+        let fst_time = parse_time(fst_input).ok_or_else(|| invalid(fst_input, EXPECTED_TIME))?;
+        expect_param(params_iter, "to", EXPECTED_TO)?;
+        let snd_input = next_param(params_iter, EXPECTED_TIME)?;
+        let snd_time =
+            parse_end_time(snd_input).ok_or_else(|| invalid(snd_input, EXPECTED_TIME))?;
+        Ok(ParamVals::TimeRange(fst_time, snd_time))
+    } else if let Some((fst_time, snd_time)) = parse_time_range(param) {
+        Ok(ParamVals::TimeRange(fst_time, snd_time))
+    } else if let Some((fst_time, snd_time)) = parse_period(param, periods) {
+        Ok(ParamVals::TimeRange(fst_time, snd_time))
+    } else if let Some(fst_time) = parse_time(param) {
         let mut times = vec![fst_time];
-        while params_iter.peek().is_some() && params_iter.peek().unwrap().parse::<u32>().is_ok() {
-            times.push(params_iter.next().unwrap().parse::<u32>().unwrap());
+        while params_iter.peek().is_some() && parse_time(params_iter.peek().unwrap()).is_some() {
+            times.push(parse_time(params_iter.next().unwrap()).unwrap());
         }
-        res.push(ParamVals::TimeCollection(times));
+        Ok(ParamVals::TimeCollection(times))
     } else {
-        return None;
+        Err(invalid(param, EXPECTED_TIMES))
+    }
+}
+
+/// Parses the value of the inputted time of day into minutes,
+/// eg. 18, 18:30, 7pm or 9:30am.
+/// Used in the parse_schedule function.
+fn parse_time(word: &str) -> Option<u32> {
+    let (clock, pm) = split_meridiem(word);
+    let (hours, minutes) = match clock.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes.parse::<u32>().ok()?),
+        None => (clock, 0),
+    };
+    let hours = hours.parse::<u32>().ok()?;
+    let hours = match pm {
+        None if hours < 24 => hours,
+        Some(pm) if (1..=12).contains(&hours) => hours % 12 + if pm { 12 } else { 0 },
+        _ => return None,
+    };
+    if minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+/// Splits am or pm from the end of a time on the 12-hour clock, eg. 7pm,
+/// along with whether it is pm, or None if the time is on the 24-hour clock.
+fn split_meridiem(word: &str) -> (&str, Option<bool>) {
+    if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (word, None)
+    }
+}
+
+/// Parses the value of the inputted time at the end of a range into minutes.
+/// A whole hour includes the entire hour, on either clock, eg. "to 20" and
+/// "to 8pm" end at 21:00, whereas "to 20:00" or "to 8:00pm" ends at 20:00.
+/// Midnight at the end of a range is the end of the day, eg. "to 12am".
+/// Used in the parse_schedule function.
+fn parse_end_time(word: &str) -> Option<u32> {
+    let time = parse_time(word)?;
+    if word.contains(':') || (time == 0 && split_meridiem(word).1.is_some()) {
+        match time {
+            0 => Some(24 * 60),
+            time => Some(time),
+        }
+    } else {
+        Some(time + 60)
     }
-    Some(res)
+}
+
+/// Parses a hyphenated range of times, eg. 19-23 or 7pm-11pm,
+/// whose end is as in parse_end_time.
+/// A start without am or pm takes the one of the end, unless that would be
+/// after the end, eg. 7-11pm starts at 19:00 but 11-1pm starts at 11:00.
+fn parse_time_range(word: &str) -> Option<(u32, u32)> {
+    let (start, end) = word.split_once('-')?;
+    let end_time = parse_end_time(end)?;
+    let start_time = match (split_meridiem(start).1, split_meridiem(end).1) {
+        (None, Some(pm)) => {
+            let with_meridiem =
+                |pm: bool| parse_time(&(start.to_string() + if pm { "pm" } else { "am" }));
+            match with_meridiem(pm) {
+                Some(time) if time < end_time => time,
+                _ => with_meridiem(!pm)?,
+            }
+        }
+        _ => parse_time(start)?,
+    };
+    Some((start_time, end_time))
+}
+
+/// Parses a date, starting with the current param, which is either a calendar date,
+/// eg. 2026-11-07, or relative to the day the query is sent, eg. tomorrow or next fri.
+/// Returns None if the param does not start a date.
+fn parse_date_spec(
+    param: &str,
+    params_iter: &mut Peekable<Iter<&str>>,
+) -> Result<Option<ParamVals>, TokenError> {
+    let date = match param {
+        "today" => RelativeDate::Today,
+        "tonight" => RelativeDate::Tonight,
+        "tomorrow" => RelativeDate::Tomorrow,
+        "next" => {
+            let word = next_param(params_iter, EXPECTED_DAY)?;
+            RelativeDate::Next(parse_day(word).map_err(|_| invalid(word, EXPECTED_DAY))?)
+        }
+        "this" => {
+            let word = next_param(params_iter, EXPECTED_WEEKEND)?;
+            if !word.starts_with("weekend") {
+                return Err(invalid(word, EXPECTED_WEEKEND));
+            }
+            RelativeDate::ThisWeekend
+        }
+        _ => return Ok(parse_date(param).map(ParamVals::Date)),
+    };
+    Ok(Some(ParamVals::RelativeDate(date)))
+}
+
+/// Parses the value of the inputted date, eg. 2026-11-03.
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
 }
 
 /// Parses the value of the inputted day.
@@ -223,3 +1093,754 @@ fn parse_day(word: &str) -> Result<Day, &str> {
         _ => Err("Invalid Date"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vals_of(query: &str) -> Option<Vec<ParamVals>> {
+        parse_query(filter_query(query)).ok().map(|(_, vals)| vals)
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(
+            vals_of("?timezone -7"),
+            Some(vec![ParamVals::TimeZone(-420)])
+        );
+        assert_eq!(
+            vals_of("?timezone -700"),
+            Some(vec![ParamVals::TimeZone(-420)])
+        );
+        assert_eq!(
+            vals_of("?timezone 12"),
+            Some(vec![ParamVals::TimeZone(720)])
+        );
+        assert_eq!(
+            vals_of("?timezone +5:30"),
+            Some(vec![ParamVals::TimeZone(330)])
+        );
+        assert_eq!(
+            vals_of("?timezone 530"),
+            Some(vec![ParamVals::TimeZone(330)])
+        );
+        assert_eq!(
+            vals_of("?timezone 5.5"),
+            Some(vec![ParamVals::TimeZone(330)])
+        );
+        assert_eq!(
+            vals_of("?timezone 5:45"),
+            Some(vec![ParamVals::TimeZone(345)])
+        );
+        assert_eq!(
+            vals_of("?timezone -3.5"),
+            Some(vec![ParamVals::TimeZone(-210)])
+        );
+        assert_eq!(vals_of("?timezone"), Some(vec![]));
+        assert_eq!(vals_of("?timezone 24"), None);
+        assert_eq!(vals_of("?timezone 5:60"), None);
+        assert_eq!(vals_of("?timezone mon"), None);
+    }
+
+    #[test]
+    fn test_parse_timezone_name() {
+        assert_eq!(
+            vals_of("?timezone America/Vancouver"),
+            Some(vec![ParamVals::TimeZoneName(
+                "America/Vancouver".to_string()
+            )])
+        );
+        assert_eq!(
+            vals_of("?timezone PST"),
+            Some(vec![ParamVals::TimeZoneName(
+                "America/Los_Angeles".to_string()
+            )])
+        );
+        assert_eq!(vals_of("?timezone Nowhere/Special"), None);
+    }
+
+    #[test]
+    fn test_parse_schedule_minutes() {
+        assert_eq!(
+            vals_of("?add fri from 18:30 to 20:00"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeRange(18 * 60 + 30, 20 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?add weekends from 18 to 23"),
+            Some(vec![
+                ParamVals::DayRange(Day::Sat, Day::Sun),
+                ParamVals::TimeRange(18 * 60, 24 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?available mon 15 9:45"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Mon]),
+                ParamVals::TimeCollection(vec![15 * 60, 9 * 60 + 45]),
+            ])
+        );
+        assert_eq!(vals_of("?add mon 24"), None);
+        assert_eq!(vals_of("?add mon 18:60"), None);
+    }
+
+    #[test]
+    fn test_parse_schedule_12_hour() {
+        let fri = ParamVals::DayCollection(vec![Day::Fri]);
+        assert_eq!(
+            vals_of("?add fri 7pm-11pm"),
+            Some(vec![fri.clone(), ParamVals::TimeRange(19 * 60, 24 * 60)])
+        );
+        assert_eq!(
+            vals_of("?add fri 7 PM – 11 PM"),
+            Some(vec![fri.clone(), ParamVals::TimeRange(19 * 60, 24 * 60)])
+        );
+        assert_eq!(
+            vals_of("?add fri 7-11pm"),
+            Some(vec![fri.clone(), ParamVals::TimeRange(19 * 60, 24 * 60)])
+        );
+        assert_eq!(
+            vals_of("?add fri 11-1pm"),
+            Some(vec![fri.clone(), ParamVals::TimeRange(11 * 60, 14 * 60)])
+        );
+        assert_eq!(
+            vals_of("?add fri 10pm-12am"),
+            Some(vec![fri.clone(), ParamVals::TimeRange(22 * 60, 24 * 60)])
+        );
+        assert_eq!(
+            vals_of("?add sat from 9:30am to 1pm"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Sat]),
+                ParamVals::TimeRange(9 * 60 + 30, 14 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?add fri 12am 12pm 1:15pm"),
+            Some(vec![
+                fri,
+                ParamVals::TimeCollection(vec![0, 12 * 60, 13 * 60 + 15]),
+            ])
+        );
+
+        // Whole hours at the end of a range include the whole last hour on
+        // either clock, so 7pm-11pm is the same range as 19-23.
+        assert_eq!(vals_of("?add fri 7pm-11pm"), vals_of("?add fri 19-23"));
+        assert_eq!(
+            vals_of("?add fri 7:00pm-11:00pm"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeRange(19 * 60, 23 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?add fri 7:00pm-11:00pm"),
+            vals_of("?add fri 19:00-23:00")
+        );
+
+        // Hyphenated ranges on the 24-hour clock include the whole last hour,
+        // the same as ranges with from and to.
+        assert_eq!(
+            vals_of("?add weekends 18-23"),
+            vals_of("?add weekends from 18 to 23")
+        );
+        assert_eq!(
+            vals_of("?remove 9:30-17:00"),
+            Some(vec![
+                ParamVals::DayRange(Day::Sun, Day::Sat),
+                ParamVals::TimeRange(9 * 60 + 30, 17 * 60),
+            ])
+        );
+        assert_eq!(vals_of("?add fri 13pm"), None);
+        assert_eq!(vals_of("?add fri 0am"), None);
+        assert_eq!(vals_of("?add fri 7pm-"), None);
+    }
+
+    #[test]
+    fn test_parse_compound_schedule() {
+        assert_eq!(
+            vals_of("?add mon-thu 19-23, fri 18-22, weekends 10-23"),
+            Some(vec![
+                ParamVals::DayRange(Day::Mon, Day::Thu),
+                ParamVals::TimeRange(19 * 60, 24 * 60),
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeRange(18 * 60, 23 * 60),
+                ParamVals::DayRange(Day::Sat, Day::Sun),
+                ParamVals::TimeRange(10 * 60, 24 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?add weekdays 18-23 except wed"),
+            Some(vec![
+                ParamVals::DayRange(Day::Mon, Day::Fri),
+                ParamVals::TimeRange(18 * 60, 24 * 60),
+                ParamVals::Except,
+                ParamVals::DayCollection(vec![Day::Wed]),
+                ParamVals::TimeRange(0, 24 * 60),
+            ])
+        );
+        // Times without days reuse the days before them.
+        assert_eq!(
+            vals_of("?remove mon tue 9-12, from 13 to 17 except tue 15"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Mon, Day::Tue]),
+                ParamVals::TimeRange(9 * 60, 13 * 60),
+                ParamVals::DayCollection(vec![Day::Mon, Day::Tue]),
+                ParamVals::TimeRange(13 * 60, 18 * 60),
+                ParamVals::Except,
+                ParamVals::DayCollection(vec![Day::Tue]),
+                ParamVals::TimeCollection(vec![15 * 60]),
+            ])
+        );
+        assert_eq!(
+            vals_of("?add fri from 22 to 2, sat 23-1"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeRange(22 * 60, 3 * 60),
+                ParamVals::DayCollection(vec![Day::Sat]),
+                ParamVals::TimeRange(23 * 60, 2 * 60),
+            ])
+        );
+        // A range which ends the hour before it starts covers the whole day.
+        assert_eq!(
+            vals_of("?add fri from 18 to 17"),
+            Some(vec![
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeRange(18 * 60, 18 * 60),
+            ])
+        );
+        assert_eq!(vals_of("?add mon 18 tue"), None);
+        assert_eq!(vals_of("?add weekdays 18-23 except"), None);
+        assert_eq!(vals_of("?available mon 18 tue 19"), None);
+    }
+
+    #[test]
+    fn test_parse_date_override() {
+        let date = NaiveDate::from_ymd_opt(2026, 11, 3).unwrap();
+        assert_eq!(
+            parse_query(filter_query("?away 2026-11-03 from 18 to 23")),
+            Ok((
+                ParamType::Away,
+                vec![
+                    ParamVals::Date(date),
+                    ParamVals::TimeRange(18 * 60, 24 * 60)
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_query(filter_query("?extra 2026-11-03 20 21")),
+            Ok((
+                ParamType::Extra,
+                vec![
+                    ParamVals::Date(date),
+                    ParamVals::TimeCollection(vec![20 * 60, 21 * 60])
+                ]
+            ))
+        );
+        assert_eq!(
+            vals_of("?away 2026-11-03"),
+            Some(vec![
+                ParamVals::Date(date),
+                ParamVals::TimeRange(0, 24 * 60)
+            ])
+        );
+        assert_eq!(
+            vals_of("?available 2026-11-03 18"),
+            Some(vec![
+                ParamVals::Date(date),
+                ParamVals::TimeCollection(vec![18 * 60])
+            ])
+        );
+        assert_eq!(vals_of("?away tue 18"), None);
+        assert_eq!(vals_of("?away 2026-13-03"), None);
+    }
+
+    #[test]
+    fn test_parse_relative_dates() {
+        let relative = |date: RelativeDate| ParamVals::RelativeDate(date);
+        assert_eq!(
+            vals_of("?away tonight"),
+            Some(vec![
+                relative(RelativeDate::Tonight),
+                ParamVals::TimeRange(18 * 60, 24 * 60)
+            ])
+        );
+        assert_eq!(
+            vals_of("?extra tomorrow from 20 to 22"),
+            Some(vec![
+                relative(RelativeDate::Tomorrow),
+                ParamVals::TimeRange(20 * 60, 23 * 60)
+            ])
+        );
+        assert_eq!(
+            vals_of("?away this weekend"),
+            Some(vec![
+                relative(RelativeDate::ThisWeekend),
+                ParamVals::TimeRange(0, 24 * 60)
+            ])
+        );
+        assert_eq!(
+            vals_of("?available next friday 20"),
+            Some(vec![
+                relative(RelativeDate::Next(Day::Fri)),
+                ParamVals::TimeCollection(vec![20 * 60])
+            ])
+        );
+        assert_eq!(
+            vals_of("?available today"),
+            Some(vec![relative(RelativeDate::Today)])
+        );
+        assert_eq!(vals_of("?available next 20"), None);
+        assert_eq!(vals_of("?away this week"), None);
+    }
+
+    #[test]
+    fn test_parse_best() {
+        assert_eq!(
+            parse_query(filter_query("?best 3h weekend")),
+            Ok((
+                ParamType::Best,
+                vec![
+                    ParamVals::Duration(180),
+                    ParamVals::DayRange(Day::Sat, Day::Sun)
+                ]
+            ))
+        );
+        assert_eq!(
+            vals_of("?best 1h30m fri sat"),
+            Some(vec![
+                ParamVals::Duration(90),
+                ParamVals::DayCollection(vec![Day::Fri, Day::Sat])
+            ])
+        );
+        assert_eq!(
+            vals_of("?best 1.5h"),
+            Some(vec![
+                ParamVals::Duration(90),
+                ParamVals::DayRange(Day::Sun, Day::Sat)
+            ])
+        );
+        assert_eq!(vals_of("?best 45m").unwrap()[0], ParamVals::Duration(45));
+        assert_eq!(vals_of("?best weekend"), None);
+        assert_eq!(vals_of("?best 2h mon 18"), None);
+    }
+
+    #[test]
+    fn test_parse_overlap() {
+        assert_eq!(
+            parse_query(filter_query("?overlap <@123> <@!456> carol weekend")),
+            Ok((
+                ParamType::Overlap,
+                vec![
+                    ParamVals::Users(vec![
+                        "123".to_string(),
+                        "456".to_string(),
+                        "carol".to_string()
+                    ]),
+                    ParamVals::DayRange(Day::Sat, Day::Sun)
+                ]
+            ))
+        );
+        assert_eq!(
+            vals_of("?overlap @alice @bob from fri to sun"),
+            Some(vec![
+                ParamVals::Users(vec!["alice".to_string(), "bob".to_string()]),
+                ParamVals::DayRange(Day::Fri, Day::Sun)
+            ])
+        );
+        assert_eq!(
+            vals_of("?overlap @alice"),
+            Some(vec![
+                ParamVals::Users(vec!["alice".to_string()]),
+                ParamVals::DayRange(Day::Sun, Day::Sat)
+            ])
+        );
+        // Users named like days are only read as days at the end of the query,
+        // and never when they are mentioned.
+        assert_eq!(
+            vals_of("?overlap sunny"),
+            Some(vec![
+                ParamVals::Users(vec!["sunny".to_string()]),
+                ParamVals::DayRange(Day::Sun, Day::Sat)
+            ])
+        );
+        assert_eq!(
+            vals_of("?overlap sunny @monty @frida sat"),
+            Some(vec![
+                ParamVals::Users(vec![
+                    "sunny".to_string(),
+                    "monty".to_string(),
+                    "frida".to_string()
+                ]),
+                ParamVals::DayCollection(vec![Day::Sat])
+            ])
+        );
+        assert_eq!(
+            vals_of("?overlap alice @satoshi"),
+            Some(vec![
+                ParamVals::Users(vec!["alice".to_string(), "satoshi".to_string()]),
+                ParamVals::DayRange(Day::Sun, Day::Sat)
+            ])
+        );
+        assert_eq!(
+            vals_of("?overlap alice \"sunny\""),
+            Some(vec![
+                ParamVals::Users(vec!["alice".to_string(), "sunny".to_string()]),
+                ParamVals::DayRange(Day::Sun, Day::Sat)
+            ])
+        );
+        assert_eq!(vals_of("?overlap"), None);
+    }
+
+    #[test]
+    fn test_parse_event() {
+        assert_eq!(
+            filter_query("?event create \"Raid night\" fri 20 3h"),
+            vec!["event", "create", "\"Raid night\"", "fri", "20", "3h"]
+        );
+        assert_eq!(
+            parse_query(filter_query("?event create \"Raid night\" fri 20 3h")),
+            Ok((
+                ParamType::CreateEvent,
+                vec![
+                    ParamVals::Title("Raid night".to_string()),
+                    ParamVals::DayCollection(vec![Day::Fri]),
+                    ParamVals::TimeCollection(vec![20 * 60]),
+                    ParamVals::Duration(180)
+                ]
+            ))
+        );
+        assert_eq!(
+            vals_of("?event create “Board games” 2026-11-03 18:30"),
+            Some(vec![
+                ParamVals::Title("Board games".to_string()),
+                ParamVals::Date(NaiveDate::from_ymd_opt(2026, 11, 3).unwrap()),
+                ParamVals::TimeCollection(vec![18 * 60 + 30]),
+                ParamVals::Duration(60)
+            ])
+        );
+        // A title in quotes is never read as the day.
+        assert_eq!(
+            vals_of("?event create \"sunday raid\" sat 20"),
+            Some(vec![
+                ParamVals::Title("sunday raid".to_string()),
+                ParamVals::DayCollection(vec![Day::Sat]),
+                ParamVals::TimeCollection(vec![20 * 60]),
+                ParamVals::Duration(60)
+            ])
+        );
+        assert_eq!(vals_of("?event create \"sunday raid\""), None);
+        assert_eq!(
+            parse_query(filter_query("?event rsvp #2 maybe")),
+            Ok((
+                ParamType::Rsvp,
+                vec![ParamVals::EventId(2), ParamVals::Rsvp(Rsvp::Maybe)]
+            ))
+        );
+        assert_eq!(
+            parse_query(filter_query("?events")),
+            Ok((ParamType::ListEvents, vec![]))
+        );
+        assert_eq!(
+            vals_of("?event cancel 2"),
+            Some(vec![ParamVals::EventId(2)])
+        );
+        assert_eq!(vals_of("?event create fri 20"), None);
+        assert_eq!(vals_of("?event create raid fri 25"), None);
+        assert_eq!(vals_of("?event rsvp 2 sure"), None);
+        assert_eq!(
+            parse_query(filter_query("?event remind 2 30 dm")),
+            Ok((
+                ParamType::Remind,
+                vec![ParamVals::EventId(2), ParamVals::Reminder(Some(30), true)]
+            ))
+        );
+        assert_eq!(
+            vals_of("?event remind 2 1h"),
+            Some(vec![
+                ParamVals::EventId(2),
+                ParamVals::Reminder(Some(60), false)
+            ])
+        );
+        assert_eq!(
+            vals_of("?event remind 2 off"),
+            Some(vec![
+                ParamVals::EventId(2),
+                ParamVals::Reminder(None, false)
+            ])
+        );
+        assert_eq!(vals_of("?event remind 2"), None);
+    }
+
+    #[test]
+    fn test_parse_schedule_id() {
+        assert_eq!(
+            vals_of("?view <@!123>"),
+            Some(vec![ParamVals::ViewId("123".to_string())])
+        );
+        assert_eq!(
+            vals_of("?view @Alice"),
+            Some(vec![ParamVals::ViewId("alice".to_string())])
+        );
+        assert_eq!(
+            vals_of("?view \"Captain Al\""),
+            Some(vec![ParamVals::ViewId("Captain Al".to_string())])
+        );
+        assert_eq!(
+            vals_of("?view captain al"),
+            Some(vec![ParamVals::ViewId("captain al".to_string())])
+        );
+        assert_eq!(vals_of("?view"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_parse_periods() {
+        assert_eq!(
+            vals_of("?add weekends evening, mon morning except mon 9"),
+            Some(vec![
+                ParamVals::DayRange(Day::Sat, Day::Sun),
+                ParamVals::TimeRange(18 * 60, 24 * 60),
+                ParamVals::DayCollection(vec![Day::Mon]),
+                ParamVals::TimeRange(6 * 60, 12 * 60),
+                ParamVals::Except,
+                ParamVals::DayCollection(vec![Day::Mon]),
+                ParamVals::TimeCollection(vec![9 * 60]),
+            ])
+        );
+        assert_eq!(
+            vals_of("?remove allday"),
+            Some(vec![
+                ParamVals::DayRange(Day::Sun, Day::Sat),
+                ParamVals::TimeRange(0, 24 * 60),
+            ])
+        );
+        assert_eq!(
+            vals_of("?away tomorrow night"),
+            Some(vec![
+                ParamVals::RelativeDate(RelativeDate::Tomorrow),
+                ParamVals::TimeRange(0, 6 * 60),
+            ])
+        );
+
+        assert_eq!(
+            parse_query(filter_query("?period")),
+            Ok((ParamType::ListPeriods, vec![]))
+        );
+        assert_eq!(
+            parse_query(filter_query("?period define RaidTime from 22 to 1")),
+            Ok((
+                ParamType::Period,
+                vec![ParamVals::Period(
+                    "raidtime".to_string(),
+                    Some((22 * 60, 2 * 60))
+                )]
+            ))
+        );
+        assert_eq!(
+            vals_of("?period remove raidtime"),
+            Some(vec![ParamVals::Period("raidtime".to_string(), None)])
+        );
+        let error_of = |query: &str| parse_query(filter_query(query)).unwrap_err();
+        assert_eq!(
+            error_of("?period define evening 19-23"),
+            QueryError::Syntax(ParamType::Period, invalid("evening", EXPECTED_PERIOD_NAME))
+        );
+        assert_eq!(
+            error_of("?period define fridays 19-23"),
+            QueryError::Syntax(ParamType::Period, invalid("fridays", EXPECTED_PERIOD_NAME))
+        );
+        assert_eq!(
+            error_of("?period define raidtime 19 20"),
+            QueryError::Syntax(ParamType::Period, invalid("19", EXPECTED_PERIOD))
+        );
+        assert_eq!(
+            error_of("?period rename raidtime"),
+            QueryError::UnknownCommand("period rename".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_periods() {
+        let mut config = GuildConfig::default();
+        config.set_period("late".to_string(), Some((22 * 60, 2 * 60)));
+        config.set_alias("free".to_string(), Some("available".to_string()));
+        let parse = |query: &str| parse_query_with_config(filter_query(query), &config);
+
+        assert_eq!(
+            parse("?add fri late"),
+            Ok((
+                ParamType::AddSchedule,
+                vec![
+                    ParamVals::DayCollection(vec![Day::Fri]),
+                    ParamVals::TimeRange(22 * 60, 2 * 60),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse("?available late"),
+            Ok((
+                ParamType::Available,
+                vec![
+                    ParamVals::DayRange(Day::Sun, Day::Sat),
+                    ParamVals::TimeRange(22 * 60, 2 * 60),
+                ]
+            ))
+        );
+        // Periods are only read where times are expected.
+        assert_eq!(
+            parse("?name late"),
+            Ok((ParamType::Name, vec![ParamVals::Name("late".to_string())]))
+        );
+        assert_eq!(
+            parse("?period define late 23-3"),
+            Ok((
+                ParamType::Period,
+                vec![ParamVals::Period(
+                    "late".to_string(),
+                    Some((23 * 60, 4 * 60))
+                )]
+            ))
+        );
+        // Without the config, the period is unknown.
+        assert!(parse_query(filter_query("?add fri late")).is_err());
+
+        // Commands and aliases cannot be hidden by periods.
+        assert_eq!(
+            parse("?period define help 19-23"),
+            Err(QueryError::Syntax(
+                ParamType::Period,
+                invalid("help", EXPECTED_PERIOD_NAME)
+            ))
+        );
+        assert_eq!(
+            parse("?period define free 19-23"),
+            Err(QueryError::Syntax(
+                ParamType::Period,
+                invalid("free", EXPECTED_PERIOD_NAME)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(
+            parse_query(filter_query("?config")),
+            Ok((ParamType::ViewConfig, vec![]))
+        );
+        assert_eq!(
+            parse_query(filter_query("?config prefix !")),
+            Ok((ParamType::Prefix, vec![ParamVals::Prefix("!".to_string())]))
+        );
+        assert_eq!(
+            vals_of("?config prefix $$"),
+            Some(vec![ParamVals::Prefix("$$".to_string())])
+        );
+        assert_eq!(
+            parse_query(filter_query("?config alias free available")),
+            Ok((
+                ParamType::Alias,
+                vec![ParamVals::Alias(
+                    "free".to_string(),
+                    Some("available".to_string())
+                )]
+            ))
+        );
+        assert_eq!(
+            vals_of("?config alias raids event list"),
+            Some(vec![ParamVals::Alias(
+                "raids".to_string(),
+                Some("event list".to_string())
+            )])
+        );
+        assert_eq!(
+            vals_of("?config alias free"),
+            Some(vec![ParamVals::Alias("free".to_string(), None)])
+        );
+
+        let error_of = |query: &str| parse_query(filter_query(query)).unwrap_err();
+        assert_eq!(
+            error_of("?config prefix !!!!!!"),
+            QueryError::Syntax(ParamType::Prefix, invalid("!!!!!!", EXPECTED_PREFIX))
+        );
+        // Letters and digits would begin ordinary messages.
+        assert_eq!(
+            error_of("?config prefix a"),
+            QueryError::Syntax(ParamType::Prefix, invalid("a", EXPECTED_PREFIX))
+        );
+        assert_eq!(
+            error_of("?config prefix \"SB!\""),
+            QueryError::Syntax(ParamType::Prefix, invalid("SB!", EXPECTED_PREFIX))
+        );
+        assert_eq!(
+            error_of("?config alias adding available"),
+            QueryError::Syntax(ParamType::Alias, invalid("adding", EXPECTED_ALIAS))
+        );
+        assert_eq!(
+            error_of("?config alias free dance"),
+            QueryError::Syntax(ParamType::Alias, invalid("dance", EXPECTED_COMMAND))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error_of = |query: &str| parse_query(filter_query(query)).unwrap_err();
+        assert_eq!(
+            error_of("?add mon from 18 to 25"),
+            QueryError::Syntax(ParamType::AddSchedule, invalid("25", EXPECTED_TIME))
+        );
+        assert_eq!(
+            error_of("?remove mon from 18"),
+            QueryError::Syntax(
+                ParamType::RemoveSchedule,
+                TokenError::Missing {
+                    expected: EXPECTED_TO
+                }
+            )
+        );
+        assert_eq!(
+            error_of("?add soon"),
+            QueryError::Syntax(
+                ParamType::AddSchedule,
+                invalid("soon", EXPECTED_DAYS_OR_TIMES)
+            )
+        );
+        assert_eq!(
+            error_of("?add mon 18 please"),
+            QueryError::Syntax(
+                ParamType::AddSchedule,
+                TokenError::Unexpected("please".to_string())
+            )
+        );
+        assert_eq!(
+            error_of("?add mon 18, tue"),
+            QueryError::Syntax(
+                ParamType::AddSchedule,
+                TokenError::Missing {
+                    expected: EXPECTED_TIMES
+                }
+            )
+        );
+        assert_eq!(
+            error_of("?best 2h mon 18"),
+            QueryError::Syntax(ParamType::Best, TokenError::Unexpected("18".to_string()))
+        );
+        assert_eq!(
+            error_of("?event create fri 20"),
+            QueryError::Syntax(ParamType::CreateEvent, invalid("fri", EXPECTED_TITLE))
+        );
+        assert_eq!(
+            error_of("?event rsvp 2 sure"),
+            QueryError::Syntax(ParamType::Rsvp, invalid("sure", EXPECTED_RSVP))
+        );
+        assert_eq!(
+            error_of("?event dance"),
+            QueryError::UnknownCommand("event dance".to_string())
+        );
+        assert_eq!(
+            error_of("?dance"),
+            QueryError::UnknownCommand("dance".to_string())
+        );
+        assert_eq!(error_of("?"), QueryError::NoCommand);
+    }
+}
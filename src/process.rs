@@ -6,273 +6,941 @@
 // Copyright (c) 2020 Philippe Nadon
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
-use crate::parse::{ParamType, ParamVals};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{Access, GuildConfig};
+use crate::day::day_range;
+use crate::error::{QueryError, TokenError, EXPECTED_TIMEZONE};
+use crate::event::{Event, DEFAULT_REMINDER};
+use crate::parse::{filter_query, parse_query_with_config, ParamType, ParamVals, PERIODS};
 use crate::schedules::ScheduleCollection;
+use crate::user::{schedule_to_string, time_to_string, User};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Handles a query sent by a user, from parsing it to processing it.
+/// If the query contains valid tokens, they are processed.
+/// Otherwise, the reply explains what went wrong and how to use the command.
+/// The user is registered if they are interacting with the bot for the first time.
+/// The config may only be changed by users with access to it.
+pub fn respond(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    account_name: &str,
+    nickname: Option<&str>,
+    channel_id: u64,
+    access: Access,
+    query: &str,
+) -> Option<String> {
+    match parse_query_with_config(filter_query(query), schedule.config()) {
+        Ok((p_type, vals)) => {
+            if let Err(why) = check_access(p_type, access) {
+                return Some(why.reply(Some(p_type), schedule.config().prefix()));
+            }
+            schedule.register_user(user_id, account_name, nickname);
+
+            match process(schedule, user_id, channel_id, p_type, vals) {
+                Ok(res) => res,
+                Err(why) => {
+                    eprintln!("Error processing message: {:?}", why);
+                    Some(why.reply(Some(p_type), schedule.config().prefix()))
+                }
+            }
+        }
+        Err(why) => {
+            eprintln!("Error parsing message: {:?}", why);
+            Some(why.reply(None, schedule.config().prefix()))
+        }
+    }
+}
+
+/// Checks whether the user may run the query, since only those who manage a guild
+/// may change its config, and nobody may change the config shared by direct messages.
+fn check_access(p_type: ParamType, access: Access) -> Result<(), QueryError> {
+    match (p_type, access) {
+        (ParamType::Prefix | ParamType::Alias | ParamType::Period, Access::DirectMessage) => {
+            Err(QueryError::ConfigInDirectMessages)
+        }
+        (ParamType::Prefix | ParamType::Alias | ParamType::Period, Access::Member) => {
+            Err(QueryError::NotManager)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Processes the extracted tokens from the user's query, at the current time.
+/// The channel is where the query was sent, which is where reminders are posted.
+pub fn process(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    channel_id: u64,
+    p_type: ParamType,
+    vals: Vec<ParamVals>,
+) -> Result<Option<String>, QueryError> {
+    process_with_clock(schedule, user_id, channel_id, p_type, vals, &SystemClock)
+}
 
 /// Processes the extracted tokens from the user's query.
 /// Mostly serves to route tokens to their corresponding process
 /// based on the type of parameter and the number of values passed.
-pub fn process(
+/// The channel is where the query was sent, which is where reminders are posted.
+/// Dates such as tomorrow are resolved in the user's timezone, according to the clock.
+pub fn process_with_clock(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
+    channel_id: u64,
     p_type: ParamType,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
-    println!(">Processing: {:?}  {:?}", p_type, vals);
+    clock: &dyn Clock,
+) -> Result<Option<String>, QueryError> {
+    let now = clock.now();
     match (p_type, vals.len()) {
-        (ParamType::TimeZone, 1) => process_set_timezone(schedule, user_name, vals),
-        (ParamType::Name, 1) => process_set_name(schedule, user_name, vals),
-        (ParamType::RemoveSchedule, 2) | (ParamType::AddSchedule, 2) => {
-            process_set_schedule(schedule, user_name, p_type, vals)
-        }
-        (ParamType::ViewSchedule, 1) => process_view_user_schedule(schedule, user_name, vals),
-        (ParamType::Available, 2) => process_available_day_time(schedule, user_name, vals),
-        (ParamType::Available, 1) => process_available_day(schedule, user_name, vals),
-        (ParamType::TimeZone, 0) => process_view_timezone(schedule, user_name),
-        (ParamType::Name, 0) => process_view_name(schedule, user_name),
-        (ParamType::ViewSchedule, 0) => process_view_schedule(schedule, user_name),
+        (ParamType::TimeZone, 1) => process_set_timezone(schedule, user_id, vals, now),
+        (ParamType::Name, 1) => process_set_name(schedule, user_id, vals),
+        (ParamType::RemoveSchedule, 2..) | (ParamType::AddSchedule, 2..) => {
+            process_set_schedule(schedule, user_id, p_type, vals)
+        }
+        (ParamType::Away, 2) | (ParamType::Extra, 2) => {
+            process_set_date_override(schedule, user_id, p_type, vals, now)
+        }
+        (ParamType::Best, 2) => process_best(schedule, user_id, vals, now),
+        (ParamType::Overlap, 2) => process_overlap(schedule, user_id, vals, now),
+        (ParamType::CreateEvent, 4) => {
+            process_create_event(schedule, user_id, channel_id, vals, now)
+        }
+        (ParamType::ListEvents, 0) => process_list_events(schedule, user_id, now),
+        (ParamType::ViewEvent, 1) => process_view_event(schedule, user_id, vals, now),
+        (ParamType::Rsvp, 2) => process_rsvp(schedule, user_id, vals, now),
+        (ParamType::CancelEvent, 1) => process_cancel_event(schedule, user_id, vals),
+        (ParamType::Remind, 2) => process_remind(schedule, user_id, vals, now),
+        (ParamType::ViewSchedule, 1) => process_view_user_schedule(schedule, user_id, vals, now),
+        (ParamType::Available, 2) => process_available_day_time(schedule, user_id, vals, now),
+        (ParamType::Available, 1) => process_available_day(schedule, user_id, vals, now),
+        (ParamType::TimeZone, 0) => process_view_timezone(schedule, user_id, now),
+        (ParamType::Name, 0) => process_view_name(schedule, user_id),
+        (ParamType::ViewSchedule, 0) => process_view_schedule(schedule, user_id, now),
         (ParamType::Meme, 0) => process_post_meme(),
-        (ParamType::Help, 0) => process_view_help(),
-        (_, _) => Err("Incorrect param type and/or param value"),
+        (ParamType::Help, 0) => process_view_help(schedule.config()),
+        (ParamType::ViewConfig, 0) => process_view_config(schedule.config()),
+        (ParamType::Prefix, 1) => process_set_prefix(schedule.config_mut(), vals),
+        (ParamType::Alias, 1) => process_set_alias(schedule.config_mut(), vals),
+        (ParamType::ListPeriods, 0) => process_view_periods(schedule.config()),
+        (ParamType::Period, 1) => process_set_period(schedule.config_mut(), vals),
+        (_, _) => Err(QueryError::Unsupported(p_type)),
     }
 }
 
 /// Sets the user's timezone.
 fn process_set_timezone(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.mut_user(user_name) {
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.mut_user(user_id) {
         match &vals[0] {
             ParamVals::TimeZone(timezone) => {
                 usr.set_timezone(*timezone);
                 Ok(None)
             }
-            _ => Err("Incorrect timezone params"),
+            ParamVals::TimeZoneName(name) => {
+                usr.set_timezone_name(name, now).map_err(|_| {
+                    QueryError::Syntax(
+                        ParamType::TimeZone,
+                        TokenError::Invalid {
+                            token: name.to_string(),
+                            expected: EXPECTED_TIMEZONE,
+                        },
+                    )
+                })?;
+                Ok(None)
+            }
+            _ => Err(QueryError::Unsupported(ParamType::TimeZone)),
         }
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
     }
 }
 
 /// Sets the user's name.
 fn process_set_name(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.mut_user(user_name) {
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.mut_user(user_id) {
         match &vals[0] {
             ParamVals::Name(name) => {
                 usr.set_name(name.to_string());
                 Ok(None)
             }
-            _ => Err("Incorrect name params"),
+            _ => Err(QueryError::Unsupported(ParamType::Name)),
         }
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
     }
 }
 
-/// Sets the user's schedule.
+/// Sets the user's schedule, one clause of days and times at a time.
+/// Times in the exceptions after the clauses are kept as they were.
+/// Nothing is changed unless every clause is supported.
 fn process_set_schedule(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     p_type: ParamType,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.mut_user(user_name) {
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.mut_user(user_id) {
         let available = p_type == ParamType::AddSchedule;
-        match (&vals[0], &vals[1]) {
-            (ParamVals::DayCollection(day_vec), ParamVals::TimeCollection(time_vec)) => {
-                day_vec.iter().for_each(|day| {
-                    time_vec
-                        .iter()
-                        .for_each(|time| usr.set_time(*day, *time, available))
-                });
-                Ok(None)
+        let mut parts = vals.split(|val| *val == ParamVals::Except);
+        let mut updated = usr.clone();
+        for clause in parts.next().unwrap_or_default().chunks(2) {
+            set_clause(&mut updated, clause, available, p_type)?;
+        }
+        if let Some(exceptions) = parts.next() {
+            let mut kept = usr.clone();
+            kept.set_raw_schedule([0; 7]);
+            for clause in exceptions.chunks(2) {
+                set_clause(&mut kept, clause, true, p_type)?;
             }
-            (ParamVals::DayCollection(day_vec), ParamVals::TimeRange(start_time, end_time)) => {
-                day_vec
-                    .iter()
-                    .for_each(|day| usr.set_time_range(*day, *start_time, *end_time, available));
-                Ok(None)
+            let (old, new, kept) = (
+                usr.get_raw_schedule(),
+                updated.get_raw_schedule(),
+                kept.get_raw_schedule(),
+            );
+            let mut merged = [0; 7];
+            for day in 0..7 {
+                merged[day] = (new[day] & !kept[day]) | (old[day] & kept[day]);
             }
-            (ParamVals::DayRange(start_day, end_day), ParamVals::TimeCollection(time_vec)) => {
+            updated.set_raw_schedule(merged);
+        }
+        *usr = updated;
+        Ok(None)
+    } else {
+        Err(QueryError::NotRegistered)
+    }
+}
+
+/// Sets a single clause of days followed by times in the user's schedule.
+fn set_clause(
+    usr: &mut User,
+    clause: &[ParamVals],
+    available: bool,
+    p_type: ParamType,
+) -> Result<(), QueryError> {
+    match clause {
+        [ParamVals::DayCollection(day_vec), ParamVals::TimeCollection(time_vec)] => {
+            day_vec.iter().for_each(|day| {
                 time_vec
                     .iter()
-                    .for_each(|time| usr.set_day_range(*start_day, *end_day, *time, available));
-                Ok(None)
+                    .for_each(|time| usr.set_time(*day, *time, available))
+            });
+        }
+        [ParamVals::DayCollection(day_vec), ParamVals::TimeRange(start_time, end_time)] => {
+            day_vec
+                .iter()
+                .for_each(|day| usr.set_time_range(*day, *start_time, *end_time, available));
+        }
+        [ParamVals::DayRange(start_day, end_day), ParamVals::TimeCollection(time_vec)] => {
+            time_vec
+                .iter()
+                .for_each(|time| usr.set_day_range(*start_day, *end_day, *time, available));
+        }
+        [ParamVals::DayRange(start_day, end_day), ParamVals::TimeRange(start_time, end_time)] => {
+            usr.set_day_time_range(*start_day, *end_day, *start_time, *end_time, available);
+        }
+        _ => return Err(QueryError::Unsupported(p_type)),
+    }
+    Ok(())
+}
+
+/// Overrides the user's schedule on a specific date.
+fn process_set_date_override(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    p_type: ParamType,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.mut_user(user_id) {
+        let available = p_type == ParamType::Extra;
+        let dates = dates_of(&vals[0], usr, now).ok_or(QueryError::Unsupported(p_type))?;
+        let ranges = match &vals[1] {
+            ParamVals::TimeCollection(time_vec) => {
+                time_vec.iter().map(|time| (*time, time + 60)).collect()
             }
-            (
-                ParamVals::DayRange(start_day, end_day),
-                ParamVals::TimeRange(start_time, end_time),
-            ) => {
-                usr.set_day_time_range(*start_day, *end_day, *start_time, *end_time, available);
-                Ok(None)
+            ParamVals::TimeRange(start_time, end_time) => vec![(*start_time, *end_time)],
+            _ => return Err(QueryError::Unsupported(p_type)),
+        };
+        for date in dates {
+            for (start_time, end_time) in &ranges {
+                usr.set_date_range(date, *start_time, *end_time, available, now);
             }
-            _ => Err("Incorrect params"),
         }
+        Ok(None)
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
+    }
+}
+
+/// Returns the dates the value refers to, resolving relative dates
+/// against the date it is for the user at that moment.
+fn dates_of(val: &ParamVals, usr: &User, now: DateTime<Utc>) -> Option<Vec<NaiveDate>> {
+    match val {
+        ParamVals::Date(date) => Some(vec![*date]),
+        ParamVals::RelativeDate(date) => Some(date.resolve(usr.utc_to_local(now).date())),
+        _ => None,
     }
 }
 
 /// Lookup another user's schedule.
 fn process_view_user_schedule(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
     match &vals[0] {
         ParamVals::ViewId(id) => {
-            if let Some(usr) = schedule.user(user_name) {
-                if let Some(lookup_usr) = schedule.user(id) {
-                    Ok(Some(
-                        "```\nTimezone:".to_string()
-                            + &lookup_usr.timezone().to_string()
-                            + "\n"
-                            + &lookup_usr.disp_schedule(true, usr.timezone())
-                            + "```",
-                    ))
-                } else {
-                    Err("Could not lookup other user")
-                }
+            if let Some(usr) = schedule.user(user_id) {
+                let lookup_usr = lookup_user(schedule, id)?;
+                Ok(Some(
+                    "```\nTimezone:".to_string()
+                        + &lookup_usr.disp_timezone(now)
+                        + "\n"
+                        + &lookup_usr.disp_schedule(true, usr.offset_at(now), now)
+                        + "```",
+                ))
             } else {
-                Err("User does not exist")
+                Err(QueryError::NotRegistered)
             }
         }
-        _ => Err("Incorrect params"),
+        _ => Err(QueryError::Unsupported(ParamType::ViewSchedule)),
     }
 }
 
-/// Check who is available at that day and time.
+/// Check who is available at that day and time, or for the whole range of times.
 fn process_available_day_time(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
     match (&vals[0], &vals[1]) {
         (ParamVals::DayCollection(day_vec), ParamVals::TimeCollection(time_vec)) => {
-            if let Some(usr) = schedule.user(user_name) {
+            if let Some(usr) = schedule.user(user_id) {
                 if day_vec.len() == 1 && time_vec.len() == 1 {
                     Ok(Some(
                         "Timezone:".to_string()
-                            + &usr.timezone().to_string()
+                            + &usr.disp_timezone(now)
                             + "\n"
                             + &schedule.available_to_string(
                                 day_vec[0],
                                 time_vec[0],
-                                usr.timezone(),
+                                usr.offset_at(now),
+                                now,
                             ),
                     ))
                 } else {
-                    Err("Too many dates")
+                    Err(QueryError::TooMany(
+                        ParamType::Available,
+                        "a single day and time",
+                    ))
                 }
             } else {
-                Err("User does not exist")
+                Err(QueryError::NotRegistered)
+            }
+        }
+        (ParamVals::Date(_) | ParamVals::RelativeDate(_), ParamVals::TimeCollection(time_vec)) => {
+            if let Some(usr) = schedule.user(user_id) {
+                if time_vec.len() == 1 {
+                    let dates = dates_of(&vals[0], usr, now).unwrap();
+                    Ok(Some(
+                        "Timezone:".to_string()
+                            + &usr.disp_timezone(noon_utc(dates[0]))
+                            + "\n"
+                            + &dates
+                                .iter()
+                                .map(|date| {
+                                    let timezone = usr.offset_at(noon_utc(*date));
+                                    schedule.available_on_to_string(*date, time_vec[0], timezone)
+                                })
+                                .collect::<String>(),
+                    ))
+                } else {
+                    Err(QueryError::TooMany(ParamType::Available, "a single time"))
+                }
+            } else {
+                Err(QueryError::NotRegistered)
+            }
+        }
+        (
+            ParamVals::DayCollection(_) | ParamVals::DayRange(_, _),
+            ParamVals::TimeRange(start_time, end_time),
+        ) => {
+            if let Some(usr) = schedule.user(user_id) {
+                let days = match &vals[0] {
+                    ParamVals::DayRange(start_day, end_day) => day_range(*start_day, *end_day),
+                    ParamVals::DayCollection(day_vec) => day_vec.clone(),
+                    _ => unreachable!(),
+                };
+                Ok(Some(
+                    "Timezone:".to_string()
+                        + &usr.disp_timezone(now)
+                        + "\n"
+                        + &days
+                            .iter()
+                            .map(|day| {
+                                schedule.available_during_to_string(
+                                    *day,
+                                    *start_time,
+                                    *end_time,
+                                    usr.offset_at(now),
+                                    now,
+                                )
+                            })
+                            .collect::<String>(),
+                ))
+            } else {
+                Err(QueryError::NotRegistered)
+            }
+        }
+        (
+            ParamVals::Date(_) | ParamVals::RelativeDate(_),
+            ParamVals::TimeRange(start_time, end_time),
+        ) => {
+            if let Some(usr) = schedule.user(user_id) {
+                let dates = dates_of(&vals[0], usr, now).unwrap();
+                Ok(Some(
+                    "Timezone:".to_string()
+                        + &usr.disp_timezone(noon_utc(dates[0]))
+                        + "\n"
+                        + &dates
+                            .iter()
+                            .map(|date| {
+                                let timezone = usr.offset_at(noon_utc(*date));
+                                schedule.available_on_during_to_string(
+                                    *date,
+                                    *start_time,
+                                    *end_time,
+                                    timezone,
+                                )
+                            })
+                            .collect::<String>(),
+                ))
+            } else {
+                Err(QueryError::NotRegistered)
             }
         }
-        _ => Err("Incorrect params"),
+        _ => Err(QueryError::Unsupported(ParamType::Available)),
     }
 }
 
 /// Check who is available during that day.
 fn process_available_day(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
+    user_id: u64,
     vals: Vec<ParamVals>,
-) -> Result<Option<String>, &'static str> {
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
     match &vals[0] {
         ParamVals::DayCollection(day_vec) => {
-            if let Some(usr) = schedule.user(user_name) {
+            if let Some(usr) = schedule.user(user_id) {
                 if day_vec.len() == 1 {
                     Ok(Some(
                         "Timezone:".to_string()
-                            + &usr.timezone().to_string()
+                            + &usr.disp_timezone(now)
                             + "\n"
-                            + &schedule.available_day_to_string(day_vec[0], usr.timezone()),
+                            + &schedule.available_day_to_string(
+                                day_vec[0],
+                                usr.offset_at(now),
+                                now,
+                            ),
                     ))
                 } else {
-                    Err("Too many dates")
+                    Err(QueryError::TooMany(ParamType::Available, "a single day"))
+                }
+            } else {
+                Err(QueryError::NotRegistered)
+            }
+        }
+        ParamVals::Date(_) | ParamVals::RelativeDate(_) => {
+            if let Some(usr) = schedule.user(user_id) {
+                let dates = dates_of(&vals[0], usr, now).unwrap();
+                Ok(Some(
+                    "Timezone:".to_string()
+                        + &usr.disp_timezone(noon_utc(dates[0]))
+                        + "\n"
+                        + &dates
+                            .iter()
+                            .map(|date| {
+                                let timezone = usr.offset_at(noon_utc(*date));
+                                schedule.available_date_to_string(*date, timezone)
+                            })
+                            .collect::<String>(),
+                ))
+            } else {
+                Err(QueryError::NotRegistered)
+            }
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Available)),
+    }
+}
+
+/// Find the best times for everyone to meet for that long.
+fn process_best(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    let days = match &vals[1] {
+        ParamVals::DayRange(start_day, end_day) => day_range(*start_day, *end_day),
+        ParamVals::DayCollection(day_vec) => day_vec.clone(),
+        _ => return Err(QueryError::Unsupported(ParamType::Best)),
+    };
+    match &vals[0] {
+        ParamVals::Duration(duration) => {
+            if let Some(usr) = schedule.user(user_id) {
+                Ok(Some(
+                    "Timezone:".to_string()
+                        + &usr.disp_timezone(now)
+                        + "\n"
+                        + &schedule.best_windows_to_string(
+                            &days,
+                            *duration,
+                            usr.offset_at(now),
+                            now,
+                        ),
+                ))
+            } else {
+                Err(QueryError::NotRegistered)
+            }
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Best)),
+    }
+}
+
+/// Show the times when all of the users are available.
+fn process_overlap(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    let days = match &vals[1] {
+        ParamVals::DayRange(start_day, end_day) => day_range(*start_day, *end_day),
+        ParamVals::DayCollection(day_vec) => day_vec.clone(),
+        _ => return Err(QueryError::Unsupported(ParamType::Overlap)),
+    };
+    match &vals[0] {
+        ParamVals::Users(names) => {
+            if let Some(usr) = schedule.user(user_id) {
+                let timezone = usr.offset_at(now);
+                let mut common = [u128::MAX; 7];
+                let mut found_names = vec![];
+                for name in names {
+                    let lookup_usr = lookup_user(schedule, name)?;
+                    let lookup_schedule = lookup_usr.local_schedule(timezone, now);
+                    for day in 0..7 {
+                        common[day] &= lookup_schedule[day];
+                    }
+                    found_names.push(lookup_usr.name());
                 }
+                Ok(Some(
+                    "```\nTimezone:".to_string()
+                        + &usr.disp_timezone(now)
+                        + "\n"
+                        + &found_names.join(", ")
+                        + "\n"
+                        + &schedule_to_string(common, false, &days)
+                        + "```",
+                ))
             } else {
-                Err("User does not exist")
+                Err(QueryError::NotRegistered)
+            }
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Overlap)),
+    }
+}
+
+/// Creates an event at the next occurrence of the day and time,
+/// or on the date, in the user's timezone.
+/// A reminder is posted in the channel shortly before it starts.
+fn process_create_event(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    channel_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    let (title, time, duration) = match (&vals[0], &vals[2], &vals[3]) {
+        (
+            ParamVals::Title(title),
+            ParamVals::TimeCollection(time_vec),
+            ParamVals::Duration(duration),
+        ) if time_vec.len() == 1 => (title.to_string(), time_vec[0], *duration),
+        _ => return Err(QueryError::Unsupported(ParamType::CreateEvent)),
+    };
+    let usr = schedule.user(user_id).ok_or(QueryError::NotRegistered)?;
+    let start = match &vals[1] {
+        ParamVals::DayCollection(day_vec) if day_vec.len() == 1 => {
+            usr.next_occurrence(day_vec[0], time, now)
+        }
+        ParamVals::Date(date) => usr.date_to_utc(*date, time),
+        _ => return Err(QueryError::Unsupported(ParamType::CreateEvent)),
+    };
+    if start < now {
+        return Err(QueryError::EventInPast);
+    }
+
+    let mut event = Event::new(title, user_id, start, duration);
+    event.set_channel(channel_id);
+    event.set_reminder(Some(DEFAULT_REMINDER), false);
+    let event_id = schedule.events_mut().insert(event, now);
+    Ok(Some(
+        "Created event #".to_string()
+            + &event_id.to_string()
+            + "\n"
+            + &view_event(schedule, user_id, event_id, now)?,
+    ))
+}
+
+/// Lists the upcoming events.
+fn process_list_events(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.user(user_id) {
+        Ok(Some(
+            "```\nTimezone:".to_string()
+                + &usr.disp_timezone(now)
+                + "\n"
+                + &schedule.events_to_string(usr, now)
+                + "```",
+        ))
+    } else {
+        Err(QueryError::NotRegistered)
+    }
+}
+
+/// View an event, along with everyone's responses.
+fn process_view_event(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    match &vals[0] {
+        ParamVals::EventId(event_id) => Ok(Some(view_event(schedule, user_id, *event_id, now)?)),
+        _ => Err(QueryError::Unsupported(ParamType::ViewEvent)),
+    }
+}
+
+/// Sets the user's response to an event.
+fn process_rsvp(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    match (&vals[0], &vals[1]) {
+        (ParamVals::EventId(event_id), ParamVals::Rsvp(rsvp)) => {
+            schedule
+                .events_mut()
+                .get_mut(*event_id)
+                .ok_or(QueryError::UnknownEvent(*event_id))?
+                .set_rsvp(user_id, *rsvp);
+            Ok(Some(view_event(schedule, user_id, *event_id, now)?))
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Rsvp)),
+    }
+}
+
+/// Cancels an event, which only its creator may do.
+fn process_cancel_event(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+) -> Result<Option<String>, QueryError> {
+    match &vals[0] {
+        ParamVals::EventId(event_id) => {
+            let event = schedule
+                .events()
+                .get(*event_id)
+                .ok_or(QueryError::UnknownEvent(*event_id))?;
+            if event.creator() != user_id {
+                return Err(QueryError::NotEventCreator(*event_id));
+            }
+            let event = schedule.events_mut().remove(*event_id).unwrap();
+            Ok(Some("Cancelled ".to_string() + &event.title()))
+        }
+        _ => Err(QueryError::Unsupported(ParamType::CancelEvent)),
+    }
+}
+
+/// Changes when an event's reminder is sent, which only its creator may do.
+/// The reminder is still posted in the channel the event was created in.
+fn process_remind(
+    schedule: &mut ScheduleCollection,
+    user_id: u64,
+    vals: Vec<ParamVals>,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    match (&vals[0], &vals[1]) {
+        (ParamVals::EventId(event_id), ParamVals::Reminder(remind_before, direct)) => {
+            let event = schedule
+                .events_mut()
+                .get_mut(*event_id)
+                .ok_or(QueryError::UnknownEvent(*event_id))?;
+            if event.creator() != user_id {
+                return Err(QueryError::NotEventCreator(*event_id));
             }
+            event.set_reminder(*remind_before, *direct);
+            Ok(Some(view_event(schedule, user_id, *event_id, now)?))
         }
-        _ => Err("Incorrect params"),
+        _ => Err(QueryError::Unsupported(ParamType::Remind)),
     }
 }
 
+/// Formats the event in the timezone of the user.
+fn view_event(
+    schedule: &ScheduleCollection,
+    user_id: u64,
+    event_id: u32,
+    now: DateTime<Utc>,
+) -> Result<String, QueryError> {
+    let usr = schedule.user(user_id).ok_or(QueryError::NotRegistered)?;
+    Ok("```\nTimezone:".to_string()
+        + &usr.disp_timezone(now)
+        + "\n"
+        + &schedule
+            .event_to_string(event_id, usr)
+            .ok_or(QueryError::UnknownEvent(event_id))?
+        + "```")
+}
+
+/// Finds a user referred to by someone else, by mention, account name,
+/// nickname or preferred name.
+/// If they cannot be found, the error suggests the closest name.
+fn lookup_user<'a>(schedule: &'a ScheduleCollection, word: &str) -> Result<&'a User, QueryError> {
+    schedule
+        .find_user(word)
+        .ok_or_else(|| QueryError::UnknownUser {
+            name: word.to_string(),
+            suggestion: schedule.suggest_name(word),
+        })
+}
+
+/// Returns the middle of the date in UTC,
+/// which is used to find the timezone someone has during that date.
+fn noon_utc(date: NaiveDate) -> DateTime<Utc> {
+    DateTime::from_naive_utc_and_offset(date.and_hms_opt(12, 0, 0).unwrap(), Utc)
+}
+
 /// View the user's timezone.
 fn process_view_timezone(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.user(user_name) {
-        Ok(Some(usr.timezone().to_string()))
+    user_id: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.user(user_id) {
+        Ok(Some(usr.disp_timezone(now)))
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
     }
 }
 
 /// View the user's name.
 fn process_view_name(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.user(user_name) {
+    user_id: u64,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.user(user_id) {
         Ok(Some(usr.name()))
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
     }
 }
 
 /// View the user's schedule.
 fn process_view_schedule(
     schedule: &mut ScheduleCollection,
-    user_name: &str,
-) -> Result<Option<String>, &'static str> {
-    if let Some(usr) = schedule.user(user_name) {
+    user_id: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, QueryError> {
+    if let Some(usr) = schedule.user(user_id) {
         Ok(Some(
             "```\n".to_string()
-                + &"Timezone:".to_string()
-                + &usr.timezone().to_string()
+                + "Timezone:"
+                + &usr.disp_timezone(now)
                 + "\n"
-                + &usr.disp_schedule(true, usr.timezone())
+                + &usr.disp_schedule(true, usr.offset_at(now), now)
+                + &match usr.disp_overrides().as_str() {
+                    "" => "".to_string(),
+                    overrides => "\nExceptions:\n".to_string() + overrides,
+                }
                 + "```",
         ))
     } else {
-        Err("Could not find user")
+        Err(QueryError::NotRegistered)
     }
 }
 
 /// Post a meme.
-fn process_post_meme() -> Result<Option<String>, &'static str> {
+fn process_post_meme() -> Result<Option<String>, QueryError> {
     Ok(Some(
         "https://i.postimg.cc/hvJh0k40/showtime.png".to_string() + "\nIt's showtime",
     ))
 }
 
-/// Displays the help info
-pub fn process_view_help() -> Result<Option<String>, &'static str> {
+/// Displays the prefix and aliases of the guild.
+fn process_view_config(config: &GuildConfig) -> Result<Option<String>, QueryError> {
+    Ok(Some(
+        "Prefix: ".to_string() + config.prefix() + "\n" + &aliases_to_string(config),
+    ))
+}
+
+/// Lists the aliases of the guild, along with the commands they run.
+fn aliases_to_string(config: &GuildConfig) -> String {
+    match config.aliases().is_empty() {
+        true => "No aliases\n".to_string(),
+        false => config
+            .aliases()
+            .iter()
+            .map(|(alias, command)| {
+                format!(
+                    "{}{} runs {}{}\n",
+                    config.prefix(),
+                    alias,
+                    config.prefix(),
+                    command
+                )
+            })
+            .fold("Aliases:\n".to_string(), |res, line| res + "- " + &line),
+    }
+}
+
+/// Sets the prefix queries begin with in the guild.
+fn process_set_prefix(
+    config: &mut GuildConfig,
+    vals: Vec<ParamVals>,
+) -> Result<Option<String>, QueryError> {
+    match &vals[0] {
+        ParamVals::Prefix(prefix) => {
+            config.set_prefix(prefix.to_string());
+            Ok(Some(format!(
+                "Queries now begin with {}, eg. {}help",
+                prefix, prefix
+            )))
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Prefix)),
+    }
+}
+
+/// Sets or removes an alias in the guild.
+fn process_set_alias(
+    config: &mut GuildConfig,
+    vals: Vec<ParamVals>,
+) -> Result<Option<String>, QueryError> {
+    match &vals[0] {
+        ParamVals::Alias(alias, command) => {
+            let previous = config.set_alias(alias.to_string(), command.clone());
+            let prefix = config.prefix();
+            Ok(Some(match (command, previous) {
+                (Some(command), _) => format!("{}{} now runs {}{}", prefix, alias, prefix, command),
+                (None, Some(_)) => format!("Removed the alias {}{}", prefix, alias),
+                (None, None) => format!("There is no alias {}{}", prefix, alias),
+            }))
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Alias)),
+    }
+}
+
+/// Lists the built-in periods, followed by those defined in the guild.
+fn process_view_periods(config: &GuildConfig) -> Result<Option<String>, QueryError> {
+    Ok(Some(
+        PERIODS
+            .iter()
+            .map(|(name, start_time, end_time)| {
+                format!("- {}: {}\n", name, range_to_string(*start_time, *end_time))
+            })
+            .fold("Periods:\n".to_string(), |res, line| res + &line)
+            + &periods_to_string(config),
+    ))
+}
+
+/// Lists the periods defined in the guild, along with their range of times.
+fn periods_to_string(config: &GuildConfig) -> String {
+    match config.periods().is_empty() {
+        true => "No periods defined in this server\n".to_string(),
+        false => config
+            .periods()
+            .iter()
+            .map(|(name, (start_time, end_time))| {
+                format!("- {}: {}\n", name, range_to_string(*start_time, *end_time))
+            })
+            .fold("Defined in this server:\n".to_string(), |res, line| {
+                res + &line
+            }),
+    }
+}
+
+/// Formats a range of times (in minutes), eg. 19:00-24:00.
+fn range_to_string(start_time: u32, end_time: u32) -> String {
+    time_to_string(start_time) + "-" + &time_to_string(end_time)
+}
+
+/// Defines or removes a period in the guild.
+fn process_set_period(
+    config: &mut GuildConfig,
+    vals: Vec<ParamVals>,
+) -> Result<Option<String>, QueryError> {
+    match &vals[0] {
+        ParamVals::Period(name, range) => {
+            let previous = config.set_period(name.to_string(), *range);
+            Ok(Some(match (range, previous) {
+                (Some((start_time, end_time)), _) => format!(
+                    "{} now means {}",
+                    name,
+                    range_to_string(*start_time, *end_time)
+                ),
+                (None, Some(_)) => format!("Removed the period {}", name),
+                (None, None) => format!("There is no period {}", name),
+            }))
+        }
+        _ => Err(QueryError::Unsupported(ParamType::Period)),
+    }
+}
+
+/// Displays the help info, with the prefix and aliases of the guild.
+pub fn process_view_help(config: &GuildConfig) -> Result<Option<String>, QueryError> {
     Ok(Some(format!(
         "
 Help:\n
 \n
 Types of inputs to commands:\n
-- time can be any from 0 to 23 (inclusive)\n
+- time can be any from 0 to 23 (inclusive), with optional minutes, eg. 18:30\n
+- time can also be on the 12-hour clock, eg. 7pm or 9:30am\n
+- a range of times can be written with a hyphen, eg. 19-23 or 7pm-11pm\n
+- a range of times includes the whole last hour, unless minutes are given, eg. 19-23:00\n
+- a range of times can also be a period, eg. evening, or one defined with {pref}period\n
 - Day can by any from sun to sat (inclusive)\n
 - you can also use 'weekends' or 'weekdays' where Day(s) applies.\n
-- timezone can be from -23 to 23\n
-- user is a discord tag, excluding the '#', case-insensitive, eg. 3ntity2051\n
-- name is anything, although it will be converted to alphanumeric lowercase\n
+- date is a specific date, eg. 2026-11-03\n
+- timezone is an offset from UTC, eg. -7, +5:30, 530 or 5.5\n
+- timezone can also be a region or abbreviation, eg. America/Vancouver or PST,\n
+  which follows daylight saving time\n
+- user is a mention, account name, nickname or name, case-insensitive, eg. @alice\n
+  names with spaces can be put in quotes, eg. \"Captain Al\"\n
+- name keeps only letters, digits and hyphens, and is lowercase unless it is in quotes\n
 \n
 Notation:\n
 - <...> represents values (eg. <time> can be 0, 2, 18...)\n
 - <add or remove> means you can use either add or remove.\n
 \n
+add, remove, view, available, timezone and name are also slash commands,\n
+eg. /add, which suggest days and users as you type.\n
+\n
 {pref}<add or remove>\n
 - add adds certain days and times as available\n
 - remove makes certain days and times as unavailable\n
@@ -283,6 +951,19 @@ Notation:\n
     - eg. {pref}add from mon to thu from 1 to 5\n
     - eg. {pref}remove mon wed fri from 4 to 7\n
     - eg. {pref}add from weekdays 1 5 18\n
+    - eg. {pref}add fri 7pm-11pm\n
+    - eg. {pref}add mon-thu 19-23, fri 18-2, weekends 10-23\n
+    - eg. {pref}add weekdays 18-23 except wed\n
+    - times past midnight continue on the next day, eg. {pref}add fri from 22 to 2\n    - times ending the hour before they start cover a whole day, eg. {pref}add fri from 18 to 17\n
+\n
+{pref}<away or extra> <date>\n
+- away makes the date unavailable, regardless of your weekly schedule\n
+- extra makes the date available, regardless of your weekly schedule\n
+    {pref}<away or extra> <date> from <time> to <time>\n
+    {pref}<away or extra> <date> <time(s)>\n
+    - eg. {pref}away 2026-11-03 from 18 to 23\n
+    - eg. {pref}extra 2026-11-05 20 21\n
+    - dates can also be today, tonight, tomorrow, this weekend or next <Day>, eg. {pref}away tonight\n
 \n
 {pref}name <name>\n
 - set your name, eg. {pref}name philio\n
@@ -290,77 +971,142 @@ Notation:\n
 - view your name\n
 \n
 {pref}timezone <timezone>\n
-- set your timezone, eg. {pref}timezone -7\n
+- set your timezone, eg. {pref}timezone -7 or {pref}timezone America/Vancouver\n
 {pref}timezone\n
 - view your timezone\n
 \n
 {pref}view <user>\n
-- view the user's schedule, eg. {pref}view 3ntity2051\n
+- view the user's schedule, eg. {pref}view @alice or {pref}view alice\n
 {pref}view\n
 - view your own schedule\n
 \n
 {pref}available <Day> <time>\n
 - see who is available on that day and time, eg. {pref}available mon 15\n
+- a range of times shows who is available for all of it, eg. {pref}available fri evening\n
 {pref}available <Day>\n
 - see who is available on that day, eg. {pref}available fri\n
+{pref}available <date> <time>\n
+{pref}available <date>\n
+- same as above, but also counts away and extra times, eg. {pref}available 2026-11-03\n
+    or {pref}available tomorrow 20\n
+\n
+{pref}best <duration> <Day(s)>\n
+- find the times the most people are available for that long, eg. {pref}best 3h weekend\n
+- duration can be in hours and/or minutes, eg. 3h, 90m or 1h30m\n
+{pref}best <duration>\n
+- same as above, for the whole week\n
+\n
+{pref}overlap <user(s)> <Day(s)>\n
+- view when all of the users are available, eg. {pref}overlap @alice @bob weekends\n
+{pref}overlap <user(s)>\n
+- same as above, for the whole week\n
+\n
+{pref}event create <title> <Day or date> <time> <duration>\n
+- plan an event, put the title in quotes, eg. {pref}event create \"Raid night\" fri 20 3h\n
+- the duration is optional, and defaults to 1h\n
+{pref}event list\n
+- view the upcoming events\n
+{pref}event view <id>\n
+- view an event and who is coming, eg. {pref}event view 3\n
+{pref}event rsvp <id> <yes, no or maybe>\n
+- respond to an event, eg. {pref}event rsvp 3 yes\n
+{pref}event cancel <id>\n
+- cancel an event you created\n
+{pref}event remind <id> <duration> dm\n
+- remind everyone coming that long before an event you created, eg. {pref}event remind 3 30\n
+- reminders are posted in the channel, 15 minutes before by default\n
+- a duration on its own is in minutes, dm also sends the reminder directly, and off removes it\n
+\n
+{pref}config\n
+- view the prefix and aliases of this server\n
+{pref}config prefix <prefix>\n
+- change the prefix of every command, eg. {pref}config prefix !\n
+- only members who can manage the server can change its config\n
+{pref}config alias <alias> <command>\n
+- add another name for a command, eg. {pref}config alias free available\n
+{pref}config alias <alias>\n
+- remove the alias\n
+\n
+{pref}period\n
+- list the periods, eg. morning, afternoon, evening, night and allday\n
+{pref}period define <name> <times>\n
+- name a range of times for this server, eg. {pref}period define raidtime 19-23\n
+- only members who can manage the server can define or remove periods\n
+{pref}period remove <name>\n
+- remove the period\n
 \n
 {pref}showtime\n
 - try it yourself!\n
 {pref}help\n
 - this message\n
-",
-        pref = "?"
+\n
+{aliases}",
+        pref = config.prefix(),
+        aliases = aliases_to_string(config),
     )))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
     use crate::day::Day;
+    use crate::event::Rsvp;
+    use crate::parse::parse_query;
+    use crate::user::hours_to_slots;
+    use chrono::TimeZone;
 
     #[test]
     fn test_schedules() {
         let mut schedule = ScheduleCollection::new();
         schedule.insert_user(123, "bob");
-        schedule.add_name_id("bob", 123).unwrap();
-        process_set_timezone(&mut schedule, "bob", vec![ParamVals::TimeZone(-5)]).unwrap();
+        process_set_timezone(
+            &mut schedule,
+            123,
+            vec![ParamVals::TimeZone(-300)],
+            Utc::now(),
+        )
+        .unwrap();
         process_set_schedule(
             &mut schedule,
-            "bob",
+            123,
             ParamType::AddSchedule,
             vec![
                 ParamVals::DayRange(Day::Sat, Day::Sun),
-                ParamVals::TimeRange(22, 23),
+                ParamVals::TimeRange(22 * 60, 24 * 60),
             ],
         )
         .unwrap();
         process_set_schedule(
             &mut schedule,
-            "bob",
+            123,
             ParamType::AddSchedule,
             vec![
                 ParamVals::DayCollection(vec![Day::Fri]),
-                ParamVals::TimeRange(22, 23),
+                ParamVals::TimeRange(22 * 60, 24 * 60),
             ],
         )
         .unwrap();
         process_set_schedule(
             &mut schedule,
-            "bob",
+            123,
             ParamType::AddSchedule,
             vec![
                 ParamVals::DayCollection(vec![Day::Mon]),
-                ParamVals::TimeRange(0, 1),
+                ParamVals::TimeRange(0, 2 * 60),
             ],
         )
         .unwrap();
         println!(
             "Schedule:\n{}",
-            schedule.user("bob").unwrap().disp_schedule(false, -5)
+            schedule
+                .user(123)
+                .unwrap()
+                .disp_schedule(false, -300, Utc::now())
         );
-        let usr_schedule = schedule.user("bob").unwrap().get_raw_schedule();
+        let usr_schedule = schedule.user(123).unwrap().get_raw_schedule();
         println!(
-            "Raw schedule:\n{:024b}\n{:024b}\n{:024b}\n{:024b}\n{:024b}\n{:024b}\n{:024b}",
+            "Raw schedule:\n{:096b}\n{:096b}\n{:096b}\n{:096b}\n{:096b}\n{:096b}\n{:096b}",
             usr_schedule[0],
             usr_schedule[1],
             usr_schedule[2],
@@ -369,6 +1115,301 @@ mod tests {
             usr_schedule[5],
             usr_schedule[6]
         );
-        assert_eq!((1 << 3) + (1 << 4), usr_schedule[6]);
+        assert_eq!(hours_to_slots((1 << 3) + (1 << 4)), usr_schedule[6]);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(123, "bob");
+        let bob = schedule.mut_user(123).unwrap();
+        bob.set_timezone(-300);
+        bob.set_day_time_range(Day::Fri, Day::Sun, 22 * 60, 24 * 60, true);
+        schedule.insert_user(456, "alice");
+        process_set_schedule(
+            &mut schedule,
+            456,
+            ParamType::AddSchedule,
+            vec![
+                ParamVals::DayRange(Day::Sun, Day::Sat),
+                ParamVals::TimeRange(3 * 60, 4 * 60),
+            ],
+        )
+        .unwrap();
+        let overlap = process_overlap(
+            &mut schedule,
+            456,
+            vec![
+                ParamVals::Users(vec!["123".to_string(), "Alice".to_string()]),
+                ParamVals::DayCollection(vec![Day::Fri, Day::Sat]),
+            ],
+            Utc::now(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(overlap.contains("bob, alice\n"));
+        assert!(overlap.contains("Fri: ░░░░░░░░░░░░░░░░░░░░░░░░\n"));
+        assert!(overlap.contains("Sat: ░░░█░░░░░░░░░░░░░░░░░░░░\n"));
+        assert!(process_overlap(
+            &mut schedule,
+            456,
+            vec![
+                ParamVals::Users(vec!["carol".to_string()]),
+                ParamVals::DayRange(Day::Sun, Day::Sat),
+            ],
+            Utc::now(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_compound_schedules() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        let mut query = |query: &str| {
+            let (p_type, vals) = parse_query(filter_query(query)).unwrap();
+            process(&mut schedule, 1, 0, p_type, vals)
+        };
+        query("?add mon 12").unwrap();
+        query("?add weekdays 18-20, sat 10 except wed").unwrap();
+        query("?remove mon-fri 19 except fri").unwrap();
+
+        let usr = schedule.user(1).unwrap();
+        let now = Utc::now();
+        assert!(usr.is_available(Day::Mon, 12 * 60, 0, now));
+        assert!(!usr.is_available(Day::Mon, 13 * 60, 0, now));
+        assert!(usr.is_available(Day::Mon, 18 * 60, 0, now));
+        assert!(!usr.is_available(Day::Mon, 19 * 60, 0, now));
+        assert!(usr.is_available(Day::Tue, 20 * 60 + 45, 0, now));
+        assert!(!usr.is_available(Day::Wed, 18 * 60, 0, now));
+        assert!(usr.is_available(Day::Fri, 19 * 60, 0, now));
+        assert!(usr.is_available(Day::Sat, 10 * 60, 0, now));
+
+        // Nothing is changed if any clause is unsupported.
+        let before = usr.get_raw_schedule();
+        assert!(process_set_schedule(
+            &mut schedule,
+            1,
+            ParamType::AddSchedule,
+            vec![
+                ParamVals::DayCollection(vec![Day::Sun]),
+                ParamVals::TimeRange(0, 60),
+                ParamVals::DayCollection(vec![Day::Sun]),
+                ParamVals::Duration(60),
+            ],
+        )
+        .is_err());
+        assert_eq!(schedule.user(1).unwrap().get_raw_schedule(), before);
+    }
+
+    #[test]
+    fn test_relative_dates() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        schedule.insert_user(2, "bob");
+        schedule.mut_user(1).unwrap().set_timezone(-8 * 60);
+        // Wed 2026-11-04 at 19:00 for alice, but already Thu in UTC.
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 11, 5, 3, 0, 0).unwrap());
+        let mut query = |user_id: u64, query: &str| {
+            let (p_type, vals) = parse_query(filter_query(query)).unwrap();
+            process_with_clock(&mut schedule, user_id, 0, p_type, vals, &clock)
+        };
+        query(1, "?extra tonight").unwrap();
+        query(1, "?extra tomorrow 20").unwrap();
+        query(1, "?away this weekend").unwrap();
+        query(2, "?extra tomorrow 9").unwrap();
+
+        let alice = schedule.user(1).unwrap();
+        let date = |day: u32| NaiveDate::from_ymd_opt(2026, 11, day).unwrap();
+        assert!(alice.is_available_on(date(4), 18 * 60, -8 * 60));
+        assert!(!alice.is_available_on(date(4), 17 * 60, -8 * 60));
+        assert!(alice.is_available_on(date(5), 20 * 60, -8 * 60));
+        assert_eq!(
+            alice.disp_overrides(),
+            "2026-11-04: extra 18:00-24:00\n\
+             2026-11-05: extra 20:00-21:00\n\
+             2026-11-07: away 00:00-24:00\n\
+             2026-11-08: away 00:00-24:00\n"
+        );
+        // It is already Thu for bob, who is in UTC.
+        assert!(schedule
+            .user(2)
+            .unwrap()
+            .is_available_on(date(6), 9 * 60, 0));
+
+        let (p_type, vals) = parse_query(filter_query("?available next thu 20")).unwrap();
+        let reply = process_with_clock(&mut schedule, 1, 0, p_type, vals, &clock)
+            .unwrap()
+            .unwrap();
+        assert!(reply.ends_with("2026-11-05 at 20:00: alice, \n"));
+    }
+
+    #[test]
+    fn test_available_ranges() {
+        let mut schedule = ScheduleCollection::new();
+        let mut query = |user_id: u64, name: &str, query: &str| {
+            respond(&mut schedule, user_id, name, None, 0, Access::Member, query)
+        };
+        query(1, "alice", "?add fri evening");
+        query(2, "bob", "?add fri 20-23, sat 0");
+        query(3, "carol", "?add fri 18-21");
+
+        assert!(query(1, "alice", "?available fri evening")
+            .unwrap()
+            .ends_with("\nFri from 18:00 to 24:00: alice, \n"));
+        assert!(query(1, "alice", "?available fri 20-21")
+            .unwrap()
+            .ends_with("\nFri from 20:00 to 22:00: alice, bob, carol, \n"));
+        // Ranges past midnight continue on the next day.
+        assert!(query(1, "alice", "?available fri from 22 to 0")
+            .unwrap()
+            .ends_with("\nFri from 22:00 to 01:00: bob, \n"));
+        assert!(query(1, "alice", "?available fri sat 20-21")
+            .unwrap()
+            .ends_with("\nFri from 20:00 to 22:00: alice, bob, carol, \n"));
+        assert!(query(1, "alice", "?available 2026-11-06 evening")
+            .unwrap()
+            .ends_with("\n2026-11-06 from 18:00 to 24:00: alice, \n"));
+    }
+
+    #[test]
+    fn test_periods() {
+        let mut schedule = ScheduleCollection::new();
+        let mut query =
+            |query: &str| respond(&mut schedule, 1, "alice", None, 0, Access::Manager, query);
+        assert_eq!(
+            query("?period define raidtime 22-1").unwrap(),
+            "raidtime now means 22:00-02:00"
+        );
+        assert_eq!(query("?add fri raidtime, sat evening"), None);
+        let periods = query("?period").unwrap();
+        assert!(periods.contains("- evening: 18:00-24:00\n"));
+        assert!(periods.ends_with("Defined in this server:\n- raidtime: 22:00-02:00\n"));
+        assert_eq!(
+            query("?period remove raidtime").unwrap(),
+            "Removed the period raidtime"
+        );
+        assert!(query("?add sun raidtime")
+            .unwrap()
+            .starts_with("Expected times"));
+
+        let usr = schedule.user(1).unwrap();
+        let now = Utc::now();
+        assert!(usr.is_available(Day::Fri, 22 * 60, 0, now));
+        assert!(usr.is_available(Day::Sat, 60 + 45, 0, now));
+        assert!(!usr.is_available(Day::Sat, 2 * 60, 0, now));
+        assert!(usr.is_available(Day::Sat, 23 * 60 + 45, 0, now));
+        assert!(!usr.is_available(Day::Sun, 0, 0, now));
+    }
+
+    #[test]
+    fn test_events() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        schedule.insert_user(2, "bob");
+        schedule.mut_user(2).unwrap().set_timezone(-7 * 60);
+
+        let created = process_create_event(
+            &mut schedule,
+            1,
+            50,
+            vec![
+                ParamVals::Title("Raid night".to_string()),
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeCollection(vec![20 * 60]),
+                ParamVals::Duration(180),
+            ],
+            Utc::now(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(created.starts_with("Created event #1\n"));
+        assert!(created.contains("#1 Raid night\n"));
+
+        process_rsvp(
+            &mut schedule,
+            2,
+            vec![ParamVals::EventId(1), ParamVals::Rsvp(Rsvp::Yes)],
+            Utc::now(),
+        )
+        .unwrap();
+        let viewed = process_view_event(&mut schedule, 2, vec![ParamVals::EventId(1)], Utc::now())
+            .unwrap()
+            .unwrap();
+        assert!(viewed.contains("Timezone:-7:00\n"));
+        assert!(viewed.contains("13:00-16:00"));
+        assert!(viewed.contains("Created by: alice\n"));
+        assert!(viewed.contains("Yes: bob, \n"));
+        assert!(process_list_events(&mut schedule, 2, Utc::now())
+            .unwrap()
+            .unwrap()
+            .contains("#1 Fri "));
+
+        assert_eq!(
+            process_rsvp(
+                &mut schedule,
+                2,
+                vec![ParamVals::EventId(2), ParamVals::Rsvp(Rsvp::No)],
+                Utc::now(),
+            ),
+            Err(QueryError::UnknownEvent(2))
+        );
+        assert_eq!(
+            process_cancel_event(&mut schedule, 2, vec![ParamVals::EventId(1)]),
+            Err(QueryError::NotEventCreator(1))
+        );
+        assert!(process_cancel_event(&mut schedule, 1, vec![ParamVals::EventId(1)]).is_ok());
+        assert!(schedule.events().get(1).is_none());
+    }
+
+    #[test]
+    fn test_view_by_name() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        schedule.insert_user(2, "bob");
+
+        assert!(process_view_user_schedule(
+            &mut schedule,
+            2,
+            vec![ParamVals::ViewId("1".to_string())],
+            Utc::now()
+        )
+        .is_ok());
+        assert_eq!(
+            process_view_user_schedule(
+                &mut schedule,
+                2,
+                vec![ParamVals::ViewId("alcie".to_string())],
+                Utc::now()
+            ),
+            Err(QueryError::UnknownUser {
+                name: "alcie".to_string(),
+                suggestion: Some("alice".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_remind() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        process_create_event(
+            &mut schedule,
+            1,
+            50,
+            vec![
+                ParamVals::Title("Raid night".to_string()),
+                ParamVals::DayCollection(vec![Day::Fri]),
+                ParamVals::TimeCollection(vec![20 * 60]),
+                ParamVals::Duration(180),
+            ],
+            Utc::now(),
+        )
+        .unwrap();
+
+        let vals = vec![ParamVals::EventId(1), ParamVals::Reminder(Some(30), true)];
+        assert!(process(&mut schedule, 1, 60, ParamType::Remind, vals).is_ok());
+        // The reminder stays in the channel the event was created in.
+        assert_eq!(schedule.events().get(1).unwrap().channel(), Some(50));
     }
 }
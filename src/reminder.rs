@@ -0,0 +1,143 @@
+//===----------------------------------------------------------------------===//
+// reminder.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::chat::ChatAdapter;
+use crate::clock::Clock;
+use crate::guilds::GuildCollection;
+
+/// How often the scheduler checks for reminders which are due, in seconds.
+pub const POLL_INTERVAL: u64 = 30;
+
+/// A reminder about an event which is due to be sent.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Reminder {
+    /// The channel the event was created in, where the reminder is sent.
+    pub channel: u64,
+    /// The text of the reminder, without any mentions.
+    pub message: String,
+    /// The unique ids of everyone coming to the event.
+    pub attendees: Vec<u64>,
+    /// Whether the reminder is also sent directly to everyone coming.
+    pub direct: bool,
+}
+
+/// Finds the reminders which are due, according to its clock.
+pub struct ReminderScheduler<C: Clock> {
+    clock: C,
+}
+
+impl<C: Clock> ReminderScheduler<C> {
+    /// Creates a scheduler which tells the time with the given clock.
+    pub fn new(clock: C) -> ReminderScheduler<C> {
+        ReminderScheduler { clock }
+    }
+
+    /// The clock, so that tests can advance it.
+    #[cfg(test)]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns every reminder which is due, marking them as sent.
+    /// The data should be saved afterwards if anything was returned,
+    /// so that reminders are not sent again after a restart.
+    pub fn poll(&self, guilds: &mut GuildCollection) -> Vec<Reminder> {
+        guilds.due_reminders(self.clock.now())
+    }
+}
+
+/// Sends the reminder to its channel, mentioning everyone coming,
+/// as well as directly to them if requested.
+/// Failures are logged, since there is nobody to reply to.
+pub fn deliver(reminder: &Reminder, adapter: &dyn ChatAdapter) {
+    let mentions = reminder
+        .attendees
+        .iter()
+        .map(|id| adapter.mention(*id))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let message = match mentions.as_str() {
+        "" => reminder.message.clone(),
+        mentions => reminder.message.clone() + "\n" + mentions,
+    };
+    if let Err(why) = adapter.send(reminder.channel, &message) {
+        eprintln!("Error sending reminder: {:?}", why);
+    }
+    if reminder.direct {
+        for id in &reminder.attendees {
+            if let Err(why) = adapter.send_direct(*id, &reminder.message) {
+                eprintln!("Error sending reminder: {:?}", why);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::MockAdapter;
+    use crate::clock::ManualClock;
+    use crate::event::{Event, Rsvp, DEFAULT_REMINDER};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_reminder_scheduler() {
+        let now = Utc.with_ymd_and_hms(2026, 11, 2, 12, 0, 0).unwrap();
+        let mut guilds = GuildCollection::new();
+        let mut event = Event::new("Raid night".to_string(), 2, now + Duration::days(1), 60);
+        event.set_channel(50);
+        event.set_reminder(Some(DEFAULT_REMINDER), false);
+        event.set_rsvp(2, Rsvp::Yes);
+        guilds.scope_mut(Some(1)).events_mut().insert(event, now);
+
+        let scheduler = ReminderScheduler::new(ManualClock::new(now));
+        assert!(scheduler.poll(&mut guilds).is_empty());
+        scheduler.clock.advance(24 * 60 - 20);
+        assert!(scheduler.poll(&mut guilds).is_empty());
+        scheduler.clock.advance(5);
+
+        let reminders = scheduler.poll(&mut guilds);
+        assert_eq!(
+            reminders,
+            vec![Reminder {
+                channel: 50,
+                message: "Reminder: Raid night starts in 15 minutes".to_string(),
+                attendees: vec![2],
+                direct: false,
+            }]
+        );
+        assert!(scheduler.poll(&mut guilds).is_empty());
+
+        // The reminders are saved, so they are not sent again after a restart.
+        let mut guilds: GuildCollection =
+            serde_json::from_str(&serde_json::to_string(&guilds).unwrap()).unwrap();
+        assert!(scheduler.poll(&mut guilds).is_empty());
+
+        let adapter = MockAdapter::default();
+        deliver(
+            &Reminder {
+                direct: true,
+                ..reminders[0].clone()
+            },
+            &adapter,
+        );
+        assert_eq!(
+            adapter.take(),
+            vec![
+                (
+                    "#50".to_string(),
+                    "Reminder: Raid night starts in 15 minutes\n@2".to_string()
+                ),
+                (
+                    "@2".to_string(),
+                    "Reminder: Raid night starts in 15 minutes".to_string()
+                ),
+            ]
+        );
+    }
+}
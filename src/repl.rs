@@ -0,0 +1,238 @@
+//===----------------------------------------------------------------------===//
+// repl.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::bot::Bot;
+use crate::chat::{Author, IncomingMessage};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// The channel queries are sent in, since there are no channels without Discord.
+const CHANNEL_ID: u64 = 1;
+
+/// The data file used by default, kept apart from the data of the bot.
+const DATA_FNAME: &str = "./repl.json";
+
+/// How to start the REPL from the command line.
+pub const USAGE: &str =
+    "Usage: scheduler_bot --repl [--user <id>] [--name <name>] [--guild <id or dm>] [--data <file>]";
+
+/// Help for the commands which only exist in the REPL.
+const REPL_HELP: &str =
+    "Queries may be typed with or without the prefix of the guild, eg. \"?\".\n\
+    :user <id> [name]  Send the following queries as another user\n\
+    :guild <id or dm>  Send the following queries in another guild, or in direct messages\n\
+    :help              Show this message\n\
+    Lines starting with # are ignored.";
+
+/// Who the queries are sent as, and where.
+/// Whoever runs the REPL keeps the data, so they manage every guild.
+#[derive(PartialEq, Debug)]
+pub struct Repl {
+    user_id: u64,
+    name: String,
+    guild_id: Option<u64>,
+    /// The data file the queries are saved to.
+    data: PathBuf,
+}
+
+impl Repl {
+    /// Reads the options from the command line,
+    /// eg. --user 1 --name alice --guild 10 --data ./test.json.
+    /// Queries are sent as user 1 ("user") in direct messages by default,
+    /// and saved to ./repl.json rather than the data of the bot.
+    pub fn from_args(args: &[String]) -> Result<Repl, String> {
+        let mut repl = Repl {
+            user_id: 1,
+            name: "user".to_string(),
+            guild_id: None,
+            data: PathBuf::from(DATA_FNAME),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| format!("Expected a value after {}", arg))
+            };
+            match arg.as_str() {
+                "--repl" => (),
+                "--user" => repl.user_id = parse_id(value()?)?,
+                "--name" => repl.name = value()?.to_string(),
+                "--guild" => repl.guild_id = parse_guild(value()?)?,
+                "--data" => repl.data = PathBuf::from(value()?),
+                _ => return Err(format!("Unknown option \"{}\"", arg)),
+            }
+        }
+        Ok(repl)
+    }
+
+    /// Returns the data file the queries are saved to.
+    pub fn data(&self) -> &Path {
+        &self.data
+    }
+
+    /// Returns the query as if it was sent by the user in the guild.
+    /// The prefix of the guild may be left out.
+    fn message(&self, line: &str, prefix: &str) -> IncomingMessage {
+        IncomingMessage {
+            author: Author {
+                id: self.user_id,
+                name: self.name.clone(),
+                nickname: None,
+            },
+            guild_id: self.guild_id,
+            channel_id: CHANNEL_ID,
+            manages_guild: true,
+            content: match line.starts_with(prefix) {
+                true => line.to_string(),
+                false => prefix.to_string() + line,
+            },
+        }
+    }
+
+    /// Handles a command which only exists in the REPL, eg. user 2 bob.
+    fn command(&mut self, command: &str) -> Result<String, String> {
+        let words = command.split_whitespace().collect::<Vec<&str>>();
+        match words.as_slice() {
+            ["user", id, ..] => {
+                self.user_id = parse_id(id)?;
+                if words.len() > 2 {
+                    self.name = words[2..].join(" ");
+                }
+                Ok(format!("Sending as {} ({})", self.name, self.user_id))
+            }
+            ["guild", guild] => {
+                self.guild_id = parse_guild(guild)?;
+                Ok(match self.guild_id {
+                    Some(id) => format!("Sending in guild {}", id),
+                    None => "Sending in direct messages".to_string(),
+                })
+            }
+            ["help"] => Ok(REPL_HELP.to_string()),
+            _ => Err(format!("Unknown command \":{}\", type :help", command)),
+        }
+    }
+}
+
+fn parse_id(word: &str) -> Result<u64, String> {
+    word.parse()
+        .map_err(|_| format!("Expected a user id, but found \"{}\"", word))
+}
+
+fn parse_guild(word: &str) -> Result<Option<u64>, String> {
+    match word {
+        "dm" => Ok(None),
+        _ => word
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Expected a guild id or dm, but found \"{}\"", word)),
+    }
+}
+
+/// Reads queries line by line, passes them to the bot, and writes the replies
+/// to the output. A prompt is written before every line if the input is interactive.
+pub fn run<R: BufRead, W: Write>(
+    repl: &mut Repl,
+    bot: &Bot,
+    input: R,
+    output: &mut W,
+    prompt: bool,
+) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "{} > ", repl.name)?;
+            output.flush()?;
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let replies = match line.strip_prefix(':') {
+            Some(command) => vec![repl.command(command).unwrap_or_else(|why| why)],
+            None => bot
+                .handle(&repl.message(line, &bot.prefix(repl.guild_id)))
+                .into_iter()
+                .map(|reply| reply.content)
+                .collect(),
+        };
+        for reply in replies {
+            writeln!(output, "{}", reply)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guilds::GuildCollection;
+    use crate::storage::MemoryStorage;
+    use crate::storage::Storage;
+    use std::sync::Arc;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        assert_eq!(
+            Repl::from_args(&args("--repl --user 2 --name bob --guild 10")),
+            Ok(Repl {
+                user_id: 2,
+                name: "bob".to_string(),
+                guild_id: Some(10),
+                data: PathBuf::from(DATA_FNAME),
+            })
+        );
+        assert_eq!(
+            Repl::from_args(&args("--repl --data ./test.json")).map(|repl| repl.data),
+            Ok(PathBuf::from("./test.json"))
+        );
+        assert!(Repl::from_args(&args("--repl --user")).is_err());
+        assert!(Repl::from_args(&args("--repl --user bob")).is_err());
+        assert!(Repl::from_args(&args("--repl --verbose")).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage.clone());
+        let mut repl = Repl::from_args(&args("--user 1 --name alice --guild 10")).unwrap();
+        let script = "# alice is free on Mon evenings\n\
+            ?add mon from 18 to 20\n\
+            :user 2 bob\n\
+            available mon 18\n\
+            ?ad mon 18\n\
+            :guild dm\n\
+            ?available mon 18\n\
+            :channel 3\n";
+        let mut output = vec![];
+        run(&mut repl, &bot, script.as_bytes(), &mut output, false).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "Sending as bob (2)");
+        assert!(output.contains("Mon at 18:00: alice"));
+        assert_eq!(
+            lines[lines.len() - 1],
+            "Unknown command \":channel 3\", type :help"
+        );
+        assert!(output.contains("Unknown command \"ad\""));
+        assert!(output.contains("Sending in direct messages"));
+
+        // Every query is saved.
+        let saved = storage.load().unwrap();
+        assert!(saved.scope(Some(10)).unwrap().user(1).is_some());
+        assert!(saved.scope(None).unwrap().user(2).is_some());
+    }
+}
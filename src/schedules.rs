@@ -6,88 +6,510 @@
 // Copyright (c) 2020 Philippe Nadon
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
+use crate::config::GuildConfig;
 use crate::day::*;
-use crate::user::User;
+use crate::event::{Event, EventCollection, Rsvp};
+use crate::reminder::Reminder;
+use crate::user::{time_to_string, wrap_end, User, SLOT_MINUTES};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-/// Contains a collection of user's schedules,
-/// as well as a mapping from their current name to their unique id.
-/// This is so that a user may refer to another user by name,
-/// while the system internally uses the user's unique id, which is static.
-#[derive(Serialize, Deserialize, Debug)]
+/// A contiguous window of time, in the timezone of the user who asked for it,
+/// during which the same group of people are all available.
+/// The end is exclusive, and may be on a later day than the start.
+#[derive(PartialEq, Debug)]
+pub struct Window {
+    /// The day and minute the window starts.
+    pub start: (Day, u32),
+    /// The day and minute the window ends.
+    pub end: (Day, u32),
+    /// The names of everyone available.
+    pub names: Vec<String>,
+}
+
+/// Contains a collection of user's schedules, by their unique id, which is static.
+/// A user may refer to another user by name, which is resolved to their id
+/// when they are looked up, so that renaming an account keeps its schedule.
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ScheduleCollection {
     users: HashMap<u64, User>,
-    name_id_map: HashMap<String, u64>,
+    #[serde(default)]
+    events: EventCollection,
+    #[serde(default)]
+    config: GuildConfig,
+}
+
+impl Default for ScheduleCollection {
+    fn default() -> ScheduleCollection {
+        ScheduleCollection::new()
+    }
 }
 
 impl ScheduleCollection {
     pub fn new() -> ScheduleCollection {
         ScheduleCollection {
             users: HashMap::new(),
-            name_id_map: HashMap::new(),
+            events: EventCollection::new(),
+            config: GuildConfig::default(),
         }
     }
 
-    /// Returns the unique id for the user, given their current name.
-    pub fn get_id(&self, name: &str) -> Option<&u64> {
-        self.name_id_map.get(name)
+    /// Reassembles a collection from its users, events and config,
+    /// as they are kept in storage.
+    pub fn from_parts(
+        users: HashMap<u64, User>,
+        events: EventCollection,
+        config: GuildConfig,
+    ) -> ScheduleCollection {
+        ScheduleCollection {
+            users,
+            events,
+            config,
+        }
     }
 
-    /// Adds a name-id pair, to keep track of which names belong to which user.
-    pub fn add_name_id(&mut self, name: &str, id: u64) -> Result<String, String> {
-        if !self.name_id_map.contains_key(name) {
-            self.name_id_map.insert(name.to_string(), id);
-            Ok("inserted new user".to_string())
-        } else {
-            Err("User name already mapped".to_string())
-        }
+    /// Returns every user, by their id.
+    pub fn users(&self) -> &HashMap<u64, User> {
+        &self.users
     }
 
-    /// Checks all current schedules, and returns a list of every user
-    /// available at that time, accounting for the timezone of the user
-    /// who sent the message.
-    pub fn available_at(&self, day: Day, time: u32, timezone: i32) -> Vec<String> {
-        self.users
+    /// Registers the user if they are interacting with the bot for the first time,
+    /// and keeps the names they may be referred to by up to date.
+    pub fn register_user(&mut self, id: u64, account_name: &str, nickname: Option<&str>) {
+        let usr = self
+            .users
+            .entry(id)
+            .or_insert_with(|| User::new(account_name.to_string()));
+        usr.set_account_name(account_name.to_string());
+        usr.set_nickname(nickname.map(|nickname| nickname.to_string()));
+    }
+
+    /// Checks all current schedules, and returns a sorted list of every user
+    /// available at that time (in minutes), accounting for the timezone (in minutes)
+    /// of the user who sent the message.
+    /// Daylight saving time is resolved for the week of the given moment.
+    pub fn available_at(
+        &self,
+        day: Day,
+        time: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut names = self
+            .users
+            .values()
+            .filter(|user| user.is_available(day, time, timezone, at))
+            .map(|user| user.name())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    /// Checks all current schedules, and returns a sorted list of every user
+    /// available at that time (in minutes) on that date, accounting for
+    /// the timezone (in minutes) of the user who sent the message,
+    /// as well as any overrides users have for the date.
+    pub fn available_on(&self, date: NaiveDate, time: u32, timezone: i32) -> Vec<String> {
+        let mut names = self
+            .users
             .values()
-            .filter(|user| user.is_available(day, time, timezone))
+            .filter(|user| user.is_available_on(date, time, timezone))
             .map(|user| user.name())
-            .collect::<Vec<String>>()
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    /// Checks all current schedules, and returns a sorted list of every user
+    /// available for the whole range of times (in minutes) on that day,
+    /// in the same way as available_at.
+    /// Ranges which end before they start continue past midnight into the next day.
+    pub fn available_during(
+        &self,
+        day: Day,
+        start_time: u32,
+        end_time: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut names = self
+            .users
+            .values()
+            .filter(|user| {
+                (start_time..wrap_end(start_time, end_time))
+                    .step_by(SLOT_MINUTES as usize)
+                    .all(|time| user.is_available(day, time, timezone, at))
+            })
+            .map(|user| user.name())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    /// Checks all current schedules, and returns a sorted list of every user
+    /// available for the whole range of times (in minutes) on that date,
+    /// in the same way as available_on.
+    pub fn available_on_during(
+        &self,
+        date: NaiveDate,
+        start_time: u32,
+        end_time: u32,
+        timezone: i32,
+    ) -> Vec<String> {
+        let mut names = self
+            .users
+            .values()
+            .filter(|user| {
+                (start_time..wrap_end(start_time, end_time))
+                    .step_by(SLOT_MINUTES as usize)
+                    .all(|time| user.is_available_on(date, time, timezone))
+            })
+            .map(|user| user.name())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
     }
 
     /// Returns a concatenation of all the times people are available on a day.
+    /// Consecutive slots with the same people available are shown as one range.
     /// Takes the timezone of the author of the message into account.
-    pub fn available_day_to_string(&self, day: Day, timezone: i32) -> String {
-        (0..24)
-            .map(|time| self.available_to_string(day, time, timezone))
-            .collect::<String>()
+    pub fn available_day_to_string(&self, day: Day, timezone: i32, at: DateTime<Utc>) -> String {
+        ranges_to_string(&day.to_string(), |time| {
+            self.available_at(day, time, timezone, at)
+        })
+    }
+
+    /// Returns a concatenation of all the times people are available on a date,
+    /// in the same format as available_day_to_string.
+    pub fn available_date_to_string(&self, date: NaiveDate, timezone: i32) -> String {
+        ranges_to_string(&date.to_string(), |time| {
+            self.available_on(date, time, timezone)
+        })
     }
 
-    /// Returns a string of the names of all users available at that time.
+    /// Returns a string of the names of all users available at that time (in minutes).
     /// Takes into account the timezone of the author of the message.
-    pub fn available_to_string(&self, day: Day, time: u32, timezone: i32) -> String {
-        let names = self.available_at(day, time, timezone);
-
-        match names.len() {
-            0 => "".to_string(),
-            _ => {
-                day.to_string()
-                    + " at "
-                    + &time.to_string()
-                    + ": "
-                    + &self
-                        .available_at(day, time, timezone)
+    pub fn available_to_string(
+        &self,
+        day: Day,
+        time: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> String {
+        names_to_string(
+            &day.to_string(),
+            time,
+            self.available_at(day, time, timezone, at),
+        )
+    }
+
+    /// Returns a string of the names of all users available at that time
+    /// on that date, in the same format as available_to_string.
+    pub fn available_on_to_string(&self, date: NaiveDate, time: u32, timezone: i32) -> String {
+        names_to_string(
+            &date.to_string(),
+            time,
+            self.available_on(date, time, timezone),
+        )
+    }
+
+    /// Returns a string of the names of all users available for the whole range
+    /// of times (in minutes) on that day, as found by available_during.
+    pub fn available_during_to_string(
+        &self,
+        day: Day,
+        start_time: u32,
+        end_time: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> String {
+        range_names_to_string(
+            &day.to_string(),
+            start_time,
+            end_time,
+            self.available_during(day, start_time, end_time, timezone, at),
+        )
+    }
+
+    /// Returns a string of the names of all users available for the whole range
+    /// of times on that date, in the same format as available_during_to_string.
+    pub fn available_on_during_to_string(
+        &self,
+        date: NaiveDate,
+        start_time: u32,
+        end_time: u32,
+        timezone: i32,
+    ) -> String {
+        range_names_to_string(
+            &date.to_string(),
+            start_time,
+            end_time,
+            self.available_on_during(date, start_time, end_time, timezone),
+        )
+    }
+
+    /// Finds the windows of at least the duration (in minutes) on the days,
+    /// during which the most people are available together.
+    /// Windows are ranked by the number of people, then by their length,
+    /// and may continue past midnight if the next day is also included.
+    /// Takes the timezone of the author of the message into account.
+    pub fn best_windows(
+        &self,
+        days: &[Day],
+        duration: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> Vec<Window> {
+        let slots = days
+            .iter()
+            .flat_map(|day| {
+                (0..24 * 60)
+                    .step_by(SLOT_MINUTES as usize)
+                    .map(move |time| (*day, time))
+            })
+            .collect::<Vec<(Day, u32)>>();
+        let names = slots
+            .iter()
+            .map(|(day, time)| self.available_at(*day, *time, timezone, at))
+            .collect::<Vec<Vec<String>>>();
+        let is_contiguous = |idx: usize| match (slots[idx], slots[idx + 1]) {
+            ((day, time), (next_day, next_time)) if day == next_day => {
+                next_time == time + SLOT_MINUTES
+            }
+            ((day, time), (next_day, next_time)) => {
+                (day as u32 + 1) % 7 == next_day as u32
+                    && time == 24 * 60 - SLOT_MINUTES
+                    && next_time == 0
+            }
+        };
+        let length = duration.div_ceil(SLOT_MINUTES).max(1) as usize;
+
+        // Every start finds the people available for the whole duration,
+        // which is then extended for as long as they all stay available.
+        let mut windows: Vec<(usize, usize, Vec<String>)> = vec![];
+        for start in 0..slots.len() {
+            if start + length > slots.len() || !(start..start + length - 1).all(is_contiguous) {
+                continue;
+            }
+            let group = names[start]
+                .iter()
+                .filter(|name| {
+                    names[start..start + length]
                         .iter()
-                        .map(move |name| name.to_string() + ", ")
+                        .all(|n| n.contains(name))
+                })
+                .cloned()
+                .collect::<Vec<String>>();
+            if group.is_empty() {
+                continue;
+            }
+            let mut end = start + length;
+            while end < slots.len()
+                && is_contiguous(end - 1)
+                && group.iter().all(|name| names[end].contains(name))
+            {
+                end += 1;
+            }
+            windows.push((start, end, group));
+        }
+
+        // Windows which are contained in a window with at least the same people are redundant.
+        let is_redundant = |window: &(usize, usize, Vec<String>)| {
+            windows.iter().any(|other| {
+                other != window
+                    && other.0 <= window.0
+                    && window.1 <= other.1
+                    && window.2.iter().all(|name| other.2.contains(name))
+            })
+        };
+        let mut best = windows
+            .iter()
+            .filter(|window| !is_redundant(window))
+            .collect::<Vec<&(usize, usize, Vec<String>)>>();
+        best.sort_by(|fst, snd| {
+            snd.2
+                .len()
+                .cmp(&fst.2.len())
+                .then((snd.1 - snd.0).cmp(&(fst.1 - fst.0)))
+                .then(fst.0.cmp(&snd.0))
+        });
+
+        best.iter()
+            .map(|(start, end, group)| {
+                let (end_day, end_time) = slots[end - 1];
+                Window {
+                    start: slots[*start],
+                    end: (end_day, end_time + SLOT_MINUTES),
+                    names: group.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a ranked list of the best windows, as found by best_windows.
+    /// Only the first few windows are shown.
+    pub fn best_windows_to_string(
+        &self,
+        days: &[Day],
+        duration: u32,
+        timezone: i32,
+        at: DateTime<Utc>,
+    ) -> String {
+        let windows = self.best_windows(days, duration, timezone, at);
+        if windows.is_empty() {
+            return "Nobody is available for that long\n".to_string();
+        }
+
+        windows
+            .iter()
+            .take(5)
+            .enumerate()
+            .map(|(rank, window)| {
+                (rank + 1).to_string()
+                    + ". "
+                    + &window.start.0.to_string()
+                    + " from "
+                    + &time_to_string(window.start.1)
+                    + " to "
+                    + &match window.end.0 == window.start.0 {
+                        true => "".to_string(),
+                        false => window.end.0.to_string() + " ",
+                    }
+                    + &time_to_string(window.end.1)
+                    + " ("
+                    + &window.names.len().to_string()
+                    + "): "
+                    + &window
+                        .names
+                        .iter()
+                        .map(|name| name.to_string() + ", ")
                         .collect::<String>()
                     + "\n"
-            }
+            })
+            .collect::<String>()
+    }
+
+    /// Retrieves an immutable reference to the events.
+    pub fn events(&self) -> &EventCollection {
+        &self.events
+    }
+
+    /// Retrieves a mutable reference to the events.
+    pub fn events_mut(&mut self) -> &mut EventCollection {
+        &mut self.events
+    }
+
+    /// Retrieves how the bot is used in the guild.
+    pub fn config(&self) -> &GuildConfig {
+        &self.config
+    }
+
+    /// Retrieves a mutable reference to how the bot is used in the guild.
+    pub fn config_mut(&mut self) -> &mut GuildConfig {
+        &mut self.config
+    }
+
+    /// Returns the reminders for every event which are due at that moment,
+    /// marking them as sent.
+    pub fn due_reminders(&mut self, at: DateTime<Utc>) -> Vec<Reminder> {
+        let ids = self.events.take_due_reminders(at);
+        ids.iter()
+            .filter_map(|id| self.events.get(*id))
+            .map(|event| {
+                // Rounded up, so that the reminder is never late.
+                let minutes = ((event.start() - at).num_seconds() + 59) / 60;
+                let (_, direct) = event.reminder();
+                Reminder {
+                    channel: event.channel().unwrap(),
+                    message: "Reminder: ".to_string()
+                        + &event.title()
+                        + &match minutes {
+                            minutes if minutes <= 0 => " has started".to_string(),
+                            1 => " starts in 1 minute".to_string(),
+                            minutes => {
+                                " starts in ".to_string() + &minutes.to_string() + " minutes"
+                            }
+                        },
+                    attendees: event.rsvps(Rsvp::Yes),
+                    direct,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns a list of the events which have not ended at that moment,
+    /// in the timezone of the user who sent the message.
+    pub fn events_to_string(&self, usr: &User, at: DateTime<Utc>) -> String {
+        let events = self.events.upcoming(at);
+        if events.is_empty() {
+            return "There are no upcoming events\n".to_string();
         }
+
+        events
+            .iter()
+            .map(|(id, event)| {
+                "#".to_string()
+                    + &id.to_string()
+                    + " "
+                    + &event_time_to_string(event, usr)
+                    + " "
+                    + &event.title()
+                    + " ("
+                    + &[Rsvp::Yes, Rsvp::Maybe, Rsvp::No]
+                        .iter()
+                        .map(|rsvp| rsvp.to_string() + ": " + &event.rsvps(*rsvp).len().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                    + ")\n"
+            })
+            .collect::<String>()
     }
 
-    /// Checks if the id corresponds to an existing user in the collection.
-    pub fn id_exists(&self, name: u64) -> bool {
-        self.users.contains_key(&name)
+    /// Returns the details of the event, including the responses of every user,
+    /// in the timezone of the user who sent the message.
+    pub fn event_to_string(&self, id: u32, usr: &User) -> Option<String> {
+        let event = self.events.get(id)?;
+        let name_of = |id: &u64| {
+            self.users
+                .get(id)
+                .map_or("unknown".to_string(), |usr| usr.name())
+        };
+
+        Some(
+            "#".to_string()
+                + &id.to_string()
+                + " "
+                + &event.title()
+                + "\n"
+                + &event_time_to_string(event, usr)
+                + "\nCreated by: "
+                + &name_of(&event.creator())
+                + "\n"
+                + &match event.reminder() {
+                    (Some(remind_before), direct) => {
+                        "Reminder: ".to_string()
+                            + &remind_before.to_string()
+                            + " minutes before"
+                            + if direct { ", sent directly" } else { "" }
+                            + "\n"
+                    }
+                    (None, _) => "".to_string(),
+                }
+                + &[Rsvp::Yes, Rsvp::Maybe, Rsvp::No]
+                    .iter()
+                    .map(|rsvp| {
+                        rsvp.to_string()
+                            + ": "
+                            + &event
+                                .rsvps(*rsvp)
+                                .iter()
+                                .map(|id| name_of(id) + ", ")
+                                .collect::<String>()
+                            + "\n"
+                    })
+                    .collect::<String>(),
+        )
     }
 
     /// Inserts a new user into the collection of schedules.
@@ -95,25 +517,313 @@ impl ScheduleCollection {
         self.users.insert(id, User::new(name.to_string()));
     }
 
-    /// Retrieves a mutable reference to a user
-    pub fn mut_user(&mut self, name: &str) -> Option<&mut User> {
-        if self.name_id_map.contains_key(name) {
-            Some(
-                self.users
-                    .entry(*self.get_id(name).unwrap())
-                    .or_insert_with(|| User::new(" ".to_string())),
-            )
-        } else {
-            None
+    /// Adds the users of another collection who are not in this one yet,
+    /// and returns how many were added.
+    pub fn merge_users(&mut self, other: ScheduleCollection) -> usize {
+        let before = self.users.len();
+        for (id, usr) in other.users {
+            self.users.entry(id).or_insert(usr);
         }
+        self.users.len() - before
+    }
+
+    /// Retrieves a mutable reference to a user.
+    pub fn mut_user(&mut self, id: u64) -> Option<&mut User> {
+        self.users.get_mut(&id)
+    }
+
+    /// Resolves a user referred to by someone else to their unique id,
+    /// either by their id (which is what a mention becomes), their account name,
+    /// their nickname or their preferred name, in that order.
+    /// Names are case-insensitive.
+    pub fn find_id(&self, word: &str) -> Option<u64> {
+        if let Some(id) = word
+            .parse::<u64>()
+            .ok()
+            .filter(|id| self.users.contains_key(id))
+        {
+            return Some(id);
+        }
+        let find_by = |name_of: &dyn Fn(&User) -> Option<String>| {
+            let mut ids = self
+                .users
+                .iter()
+                .filter(|(_, usr)| name_of(usr).is_some_and(|name| name.eq_ignore_ascii_case(word)))
+                .map(|(id, _)| *id)
+                .collect::<Vec<u64>>();
+            // Several users may share a nickname or preferred name,
+            // so the result should not depend on the order of the map.
+            ids.sort_unstable();
+            ids.first().copied()
+        };
+        find_by(&|usr| Some(usr.account_name()))
+            .or_else(|| find_by(&|usr| usr.nickname()))
+            .or_else(|| find_by(&|usr| Some(usr.name())))
+    }
+
+    /// Finds the name closest to the word, for when a user could not be found,
+    /// eg. "alcie" is close to "alice", and "ali" is the start of "alice".
+    /// Returns None if no name is close enough.
+    pub fn suggest_name(&self, word: &str) -> Option<String> {
+        let word = word.to_lowercase();
+        let max_distance = ((word.chars().count() + 1) / 3).max(1);
+        self.users
+            .values()
+            .flat_map(|usr| {
+                vec![Some(usr.account_name()), usr.nickname(), Some(usr.name())]
+                    .into_iter()
+                    .flatten()
+            })
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let lowercase = name.to_lowercase();
+                let distance = match word.chars().count() >= 3 && lowercase.starts_with(&word) {
+                    true => 1,
+                    false => edit_distance(&word, &lowercase),
+                };
+                (distance, name)
+            })
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, name)| name)
+    }
+
+    /// Finds a user referred to by someone else, see find_id.
+    pub fn find_user(&self, word: &str) -> Option<&User> {
+        self.find_id(word).and_then(|id| self.users.get(&id))
     }
 
     /// Retrieves an immutable reference to a user.
-    pub fn user(&self, name: &str) -> Option<&User> {
-        if self.name_id_map.contains_key(name) {
-            self.users.get(self.get_id(name).unwrap())
-        } else {
-            None
+    pub fn user(&self, id: u64) -> Option<&User> {
+        self.users.get(&id)
+    }
+}
+
+/// Returns the number of characters which must be inserted, removed or replaced
+/// to turn one word into the other.
+fn edit_distance(fst: &str, snd: &str) -> usize {
+    let snd = snd.chars().collect::<Vec<char>>();
+    let mut prev_row = (0..=snd.len()).collect::<Vec<usize>>();
+    for (i, fst_chr) in fst.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, snd_chr) in snd.iter().enumerate() {
+            let replace = prev_row[j] + if fst_chr == *snd_chr { 0 } else { 1 };
+            row.push(replace.min(prev_row[j + 1] + 1).min(row[j] + 1));
         }
+        prev_row = row;
+    }
+    prev_row[snd.len()]
+}
+
+/// Formats when the event happens in the user's timezone,
+/// eg. "Fri 2026-11-06 20:00-23:00".
+fn event_time_to_string(event: &Event, usr: &User) -> String {
+    usr.utc_to_local(event.start())
+        .format("%a %Y-%m-%d %H:%M")
+        .to_string()
+        + "-"
+        + &usr.utc_to_local(event.end()).format("%H:%M").to_string()
+}
+
+/// Formats the names of the users available at a time (in minutes) on a day,
+/// or an empty string if nobody is available.
+fn names_to_string(day: &str, time: u32, names: Vec<String>) -> String {
+    match names.len() {
+        0 => "".to_string(),
+        _ => {
+            day.to_string()
+                + " at "
+                + &time_to_string(time)
+                + ": "
+                + &names
+                    .iter()
+                    .map(move |name| name.to_string() + ", ")
+                    .collect::<String>()
+                + "\n"
+        }
+    }
+}
+
+/// Formats the users available for the whole range of times (in minutes),
+/// in the same format as names_to_string.
+fn range_names_to_string(day: &str, start_time: u32, end_time: u32, names: Vec<String>) -> String {
+    match names.len() {
+        0 => "".to_string(),
+        _ => {
+            day.to_string()
+                + " from "
+                + &time_to_string(start_time)
+                + " to "
+                + &time_to_string(end_time)
+                + ": "
+                + &names
+                    .iter()
+                    .map(move |name| name.to_string() + ", ")
+                    .collect::<String>()
+                + "\n"
+        }
+    }
+}
+
+/// Formats the users available throughout a day, given the users available
+/// at each time (in minutes).
+/// Consecutive slots with the same people available are shown as one range.
+fn ranges_to_string(day: &str, available_at: impl Fn(u32) -> Vec<String>) -> String {
+    let mut res = String::new();
+    let mut start_time = 0;
+    let mut names = available_at(start_time);
+
+    for time in (SLOT_MINUTES..=24 * 60).step_by(SLOT_MINUTES as usize) {
+        let next_names = match time {
+            t if t < 24 * 60 => available_at(time),
+            _ => vec![],
+        };
+        if next_names != names {
+            if !names.is_empty() {
+                res += &(day.to_string()
+                    + " from "
+                    + &time_to_string(start_time)
+                    + " to "
+                    + &time_to_string(time)
+                    + ": "
+                    + &names
+                        .iter()
+                        .map(|name| name.to_string() + ", ")
+                        .collect::<String>()
+                    + "\n");
+            }
+            start_time = time;
+            names = next_names;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+
+    #[test]
+    fn test_available_day_to_string() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.insert_user(1, "alice");
+        schedule.insert_user(2, "bob");
+        let alice = schedule.mut_user(1).unwrap();
+        alice.set_time_range(Day::Fri, 18 * 60 + 30, 20 * 60, true);
+        let bob = schedule.mut_user(2).unwrap();
+        bob.set_timezone(-60);
+        bob.set_time_range(Day::Fri, 18 * 60, 23 * 60, true);
+
+        assert_eq!(
+            schedule.available_day_to_string(Day::Fri, 0, Utc::now()),
+            "Fri from 18:30 to 19:00: alice, \n\
+             Fri from 19:00 to 20:00: alice, bob, \n\
+             Fri from 20:00 to 24:00: bob, \n"
+        );
+    }
+
+    #[test]
+    fn test_best_windows() {
+        let mut schedule = ScheduleCollection::new();
+        for (id, name) in ["alice", "bob", "carol"].iter().enumerate() {
+            schedule.insert_user(id as u64, name);
+        }
+        let alice = schedule.mut_user(0).unwrap();
+        alice.set_day_time_range(Day::Sat, Day::Sun, 18 * 60, 24 * 60, true);
+        alice.set_time_range(Day::Sun, 0, 2 * 60, true);
+        let bob = schedule.mut_user(1).unwrap();
+        bob.set_timezone(-60);
+        bob.set_time_range(Day::Sat, 19 * 60, 24 * 60, true);
+        bob.set_time_range(Day::Sun, 0, 60, true);
+        let carol = schedule.mut_user(2).unwrap();
+        carol.set_time_range(Day::Sat, 21 * 60, 23 * 60, true);
+        carol.set_time_range(Day::Mon, 21 * 60, 23 * 60, true);
+
+        let windows = schedule.best_windows(&[Day::Sat, Day::Sun], 2 * 60, 0, Utc::now());
+        assert_eq!(
+            windows[0],
+            Window {
+                start: (Day::Sat, 21 * 60),
+                end: (Day::Sat, 23 * 60),
+                names: vec!["alice".to_string(), "bob".to_string(), "carol".to_string()],
+            }
+        );
+        assert_eq!(
+            windows[1],
+            Window {
+                start: (Day::Sat, 20 * 60),
+                end: (Day::Sun, 2 * 60),
+                names: vec!["alice".to_string(), "bob".to_string()],
+            }
+        );
+        assert_eq!(
+            schedule
+                .best_windows_to_string(&[Day::Sat, Day::Sun], 3 * 60, 0, Utc::now())
+                .lines()
+                .next()
+                .unwrap(),
+            "1. Sat from 20:00 to Sun 02:00 (2): alice, bob, "
+        );
+        assert!(schedule
+            .best_windows(&[Day::Mon], 3 * 60, 0, Utc::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_identity() {
+        // Older data files mapped every name a user ever had to their id.
+        let mut guilds = schema::parse(
+            r#"{
+                "users": {
+                    "1": {"name": "ali", "utc_offset": 0, "slots": [0, 0, 0, 0, 0, 1, 0]},
+                    "2": {"name": "bob", "utc_offset": 0, "slots": [0, 0, 0, 0, 0, 0, 0]}
+                },
+                "name_id_map": {"alice_old": 1, "alice_new": 1, "bob": 2, "ghost": 3}
+            }"#,
+        )
+        .unwrap();
+        guilds.move_legacy(10).unwrap();
+        let schedule = guilds.scope_mut(Some(10));
+        assert_eq!(schedule.user(1).unwrap().account_name(), "alice_new");
+        assert_eq!(schedule.find_id("Alice_New"), Some(1));
+        assert_eq!(schedule.find_id("ghost"), None);
+        assert!(!serde_json::to_string(&schedule)
+            .unwrap()
+            .contains("name_id_map"));
+
+        // Renaming an account keeps the schedule.
+        schedule.register_user(1, "alice", Some("Captain"));
+        assert_eq!(schedule.user(1).unwrap().get_raw_schedule()[5], 1);
+        assert_eq!(schedule.find_id("alice"), Some(1));
+        assert_eq!(schedule.find_id("alice_new"), None);
+        assert_eq!(schedule.find_id("captain"), Some(1));
+        assert_eq!(schedule.find_id("ali"), Some(1));
+        assert_eq!(schedule.find_id("2"), Some(2));
+
+        // A new user may take an old name without colliding with anyone.
+        schedule.register_user(3, "alice_new", None);
+        schedule.mut_user(3).unwrap().set_name("bob".to_string());
+        assert_eq!(schedule.find_id("alice_new"), Some(3));
+        assert_eq!(schedule.find_id("bob"), Some(2));
+        assert_eq!(schedule.user(1).unwrap().name(), "ali");
+    }
+
+    #[test]
+    fn test_suggest_name() {
+        let mut schedule = ScheduleCollection::new();
+        schedule.register_user(1, "alice", Some("Captain Al"));
+        schedule.register_user(2, "bob.smith", None);
+        assert_eq!(edit_distance("alcie", "alice"), 2);
+        assert_eq!(edit_distance("", "bob"), 3);
+        assert_eq!(schedule.suggest_name("alcie"), Some("alice".to_string()));
+        assert_eq!(schedule.suggest_name("alicee"), Some("alice".to_string()));
+        assert_eq!(schedule.suggest_name("bob"), Some("bob.smith".to_string()));
+        assert_eq!(
+            schedule.suggest_name("captain"),
+            Some("Captain Al".to_string())
+        );
+        assert_eq!(schedule.suggest_name("carol"), None);
+        assert_eq!(schedule.suggest_name("al"), None);
     }
 }
@@ -0,0 +1,434 @@
+//===----------------------------------------------------------------------===//
+// schema.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::config::GuildConfig;
+use crate::event::EventCollection;
+use crate::guilds::GuildCollection;
+use crate::schedules::ScheduleCollection;
+use crate::user::{hours_to_slots, shift_schedule, StoredUser, User, SLOT_MINUTES};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The version of the data format written by this version of the bot.
+/// Whenever the format changes, the version is increased and a migration
+/// from the previous version is added to MIGRATIONS.
+///
+/// 0. A single collection of schedules, shared by every guild.
+///    Timezones are in hours, and schedules have one bit per hour.
+/// 1. A separate collection of schedules for every guild.
+/// 2. Timezones are in minutes (utc_offset).
+/// 3. Schedules have one bit per slot (slots).
+/// 4. Users keep their account name, instead of a mapping from names to ids.
+///    The version is saved along with the data.
+/// 5. Every guild has a config, with its prefix and aliases.
+/// 6. Guild configs have custom periods (periods).
+pub const VERSION: u32 = 6;
+
+/// Upgrades the data by a single version, the migration at index N
+/// upgrades version N to version N + 1.
+/// Data saved before the version was saved is read as version 0 or 1,
+/// so every migration must leave data which is already upgraded unchanged.
+const MIGRATIONS: [fn(&mut StoredData); VERSION as usize] = [
+    into_guilds,
+    timezone_in_minutes,
+    schedule_in_slots,
+    account_names,
+    guild_config,
+    guild_periods,
+];
+
+/// The data as it is read from storage, in any version of the format.
+/// Fields which are no longer used are kept until they are migrated.
+#[derive(Deserialize)]
+pub struct StoredData {
+    #[serde(default)]
+    version: Option<u32>,
+    #[serde(default)]
+    guilds: HashMap<u64, StoredCollection>,
+    #[serde(default)]
+    direct_messages: Option<StoredCollection>,
+    #[serde(default)]
+    legacy: Option<StoredCollection>,
+    /// The single collection of schedules, in version 0.
+    #[serde(default)]
+    users: Option<HashMap<u64, StoredUser>>,
+    #[serde(default)]
+    name_id_map: HashMap<String, u64>,
+}
+
+/// A collection of schedules as it is read from storage, see StoredData.
+#[derive(Deserialize)]
+pub struct StoredCollection {
+    users: HashMap<u64, StoredUser>,
+    /// The mapping from names to ids, before version 4.
+    #[serde(default)]
+    name_id_map: HashMap<String, u64>,
+    #[serde(default)]
+    events: EventCollection,
+    #[serde(default)]
+    config: GuildConfig,
+}
+
+/// The data as it is saved, along with the version of the format.
+#[derive(Serialize)]
+struct VersionedData<'a> {
+    version: u32,
+    #[serde(flatten)]
+    guilds: &'a GuildCollection,
+}
+
+impl StoredData {
+    /// Reassembles the data from its scopes, as they are kept in a database.
+    #[cfg(feature = "sqlite")]
+    pub fn from_scopes(
+        version: u32,
+        guilds: HashMap<u64, StoredCollection>,
+        direct_messages: StoredCollection,
+        legacy: Option<StoredCollection>,
+    ) -> StoredData {
+        StoredData {
+            version: Some(version),
+            guilds,
+            direct_messages: Some(direct_messages),
+            legacy,
+            users: None,
+            name_id_map: HashMap::new(),
+        }
+    }
+
+    /// Returns every collection of schedules in the data.
+    fn collections_mut(&mut self) -> Vec<&mut StoredCollection> {
+        self.guilds
+            .values_mut()
+            .chain(self.direct_messages.iter_mut())
+            .chain(self.legacy.iter_mut())
+            .collect()
+    }
+
+    /// Returns every user in the data.
+    fn users_mut(&mut self) -> Vec<&mut StoredUser> {
+        self.collections_mut()
+            .into_iter()
+            .flat_map(|schedule| schedule.users.values_mut())
+            .collect()
+    }
+}
+
+impl StoredCollection {
+    pub fn new(
+        users: HashMap<u64, StoredUser>,
+        events: EventCollection,
+        config: GuildConfig,
+    ) -> StoredCollection {
+        StoredCollection {
+            users,
+            name_id_map: HashMap::new(),
+            events,
+            config,
+        }
+    }
+
+    /// Assumes the collection was migrated to the current version of the format.
+    fn into_schedule(self) -> ScheduleCollection {
+        ScheduleCollection::from_parts(
+            self.users
+                .into_iter()
+                .map(|(id, usr)| (id, User::from(usr)))
+                .collect(),
+            self.events,
+            self.config,
+        )
+    }
+}
+
+/// Parses data saved by any version of the bot.
+pub fn parse(serialized: &str) -> Result<GuildCollection, String> {
+    serde_json::from_str(serialized)
+        .map_err(|why| why.to_string())
+        .and_then(migrate)
+}
+
+/// Serializes the data, along with the current version of the format.
+pub fn to_string(guilds: &GuildCollection) -> serde_json::Result<String> {
+    serde_json::to_string(&VersionedData {
+        version: VERSION,
+        guilds,
+    })
+}
+
+/// Returns the version of the data, which is guessed if it was not saved.
+fn version_of(data: &StoredData) -> u32 {
+    match (data.version, &data.users) {
+        (Some(version), _) => version,
+        (None, Some(_)) => 0,
+        (None, None) => 1,
+    }
+}
+
+/// Upgrades the data to the current version of the format.
+/// Data from a newer version of the bot is refused, rather than losing
+/// whatever the newer version added.
+pub fn migrate(mut data: StoredData) -> Result<GuildCollection, String> {
+    let version = version_of(&data);
+    if version > VERSION {
+        return Err(format!(
+            "The data is version {}, but only versions up to {} can be read",
+            version, VERSION
+        ));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut data);
+    }
+
+    let direct_messages = data
+        .direct_messages
+        .ok_or_else(|| "The data does not contain any schedules".to_string())?;
+    Ok(GuildCollection::from_scopes(
+        data.guilds
+            .into_iter()
+            .map(|(id, schedule)| (id, schedule.into_schedule()))
+            .collect(),
+        direct_messages.into_schedule(),
+        data.legacy.map(StoredCollection::into_schedule),
+    ))
+}
+
+/// Version 0 to 1: the single collection is kept apart from every guild,
+/// until it is moved into the guild it was used in.
+fn into_guilds(data: &mut StoredData) {
+    if let Some(users) = data.users.take() {
+        eprintln!("Found data file without guilds. Keeping it apart from every guild...");
+        data.legacy = Some(StoredCollection {
+            users,
+            name_id_map: std::mem::take(&mut data.name_id_map),
+            events: EventCollection::new(),
+            config: GuildConfig::default(),
+        });
+        data.direct_messages = Some(StoredCollection::new(
+            HashMap::new(),
+            EventCollection::new(),
+            GuildConfig::default(),
+        ));
+    }
+}
+
+/// Version 1 to 2: timezones are moved from hours to minutes.
+fn timezone_in_minutes(data: &mut StoredData) {
+    for usr in data.users_mut() {
+        if let Some(hours) = usr.timezone.take() {
+            usr.utc_offset.get_or_insert(hours * 60);
+        }
+    }
+}
+
+/// Version 2 to 3: every hour of the schedule becomes the slots it contains.
+fn schedule_in_slots(data: &mut StoredData) {
+    for usr in data.users_mut() {
+        if let Some(hours) = usr.schedule.take() {
+            // Hourly schedules placed local hours in the UTC hour they
+            // started in, so partial hour timezones are moved back in line.
+            let mut slots = [0; 7];
+            for day in 0..7 {
+                slots[day] = hours_to_slots(hours[day]);
+            }
+            let timezone = usr.utc_offset.unwrap_or(0);
+            usr.slots.get_or_insert(shift_schedule(
+                slots,
+                (-timezone).rem_euclid(60) / SLOT_MINUTES as i32,
+            ));
+        }
+    }
+}
+
+/// Version 3 to 4: users who do not have an account name yet are given one
+/// of the names which were mapped to their id.
+fn account_names(data: &mut StoredData) {
+    for schedule in data.collections_mut() {
+        // A user may have been mapped by several names if they renamed their account,
+        // in which case any of them will do until they send another message.
+        let mut names = std::mem::take(&mut schedule.name_id_map)
+            .into_iter()
+            .collect::<Vec<(String, u64)>>();
+        names.sort();
+        for (name, id) in names {
+            if let Some(usr) = schedule.users.get_mut(&id) {
+                if usr.account_name.is_empty() {
+                    usr.account_name = name;
+                }
+            }
+        }
+    }
+}
+
+/// Version 4 to 5: guilds are given the default config.
+/// Guilds without a config are read with the default one, so there is nothing to change.
+fn guild_config(_data: &mut StoredData) {}
+
+/// Version 5 to 6: guild configs are given custom periods.
+/// Configs without periods are read without any, so there is nothing to change.
+fn guild_periods(_data: &mut StoredData) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day::Day;
+    use crate::event::Rsvp;
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+    /// Loads a fixture, and checks that alice and bob were migrated.
+    /// Alice is free on Mon from 18:00 to 19:00 in her timezone (-7:00 or -6:30),
+    /// and bob is free on Tue at 20:00 UTC.
+    /// Data from before guilds is moved into the guild, as it would be with --legacy-guild.
+    fn load_fixture(serialized: &str, guild: Option<u64>, utc_offset: i32) -> GuildCollection {
+        let mut guilds = parse(serialized).unwrap();
+        if let (Some(id), Some(_)) = (guild, guilds.legacy()) {
+            guilds.move_legacy(id).unwrap();
+        }
+        let schedule = guilds.scope_mut(guild);
+        let alice = schedule.find_user("alice").unwrap();
+        assert_eq!(alice.timezone(), utc_offset);
+        assert!(alice.is_available(Day::Mon, 18 * 60, utc_offset, Utc::now()));
+        assert!(alice.is_available(Day::Mon, 18 * 60 + 45, utc_offset, Utc::now()));
+        assert!(!alice.is_available(Day::Mon, 19 * 60, utc_offset, Utc::now()));
+        assert!(!alice.is_available(Day::Mon, 17 * 60 + 45, utc_offset, Utc::now()));
+        assert_eq!(schedule.find_id("bob"), Some(2));
+        assert!(schedule
+            .user(2)
+            .unwrap()
+            .is_available(Day::Tue, 20 * 60, 0, Utc::now()));
+
+        // Saving and loading again keeps everything.
+        let serialized = to_string(&guilds).unwrap();
+        assert!(serialized.starts_with(&format!("{{\"version\":{}", VERSION)));
+        assert!(!serialized.contains("name_id_map"));
+        let mut reloaded = parse(&serialized).unwrap();
+        assert_eq!(
+            reloaded
+                .scope_mut(guild)
+                .user(1)
+                .unwrap()
+                .get_raw_schedule(),
+            guilds.scope_mut(guild).user(1).unwrap().get_raw_schedule()
+        );
+        guilds
+    }
+
+    #[test]
+    fn test_version_0() {
+        let serialized = include_str!("../fixtures/v0_global.json");
+        let mut guilds = parse(serialized).unwrap();
+        assert!(guilds.legacy().unwrap().user(1).is_some());
+        assert!(guilds.scope_mut(Some(10)).user(1).is_none());
+
+        let mut guilds = load_fixture(serialized, Some(10), -420);
+        assert!(guilds.legacy().is_none());
+        assert!(guilds.scope_mut(Some(20)).user(1).is_none());
+        assert!(guilds.scope_mut(None).user(1).is_none());
+    }
+
+    #[test]
+    fn test_version_1() {
+        let mut guilds = load_fixture(include_str!("../fixtures/v1_guilds.json"), Some(10), -420);
+        assert!(guilds.scope_mut(Some(20)).user(1).is_none());
+    }
+
+    #[test]
+    fn test_version_2() {
+        load_fixture(
+            include_str!("../fixtures/v2_utc_offset.json"),
+            Some(10),
+            -390,
+        );
+        let mut guilds = load_fixture(
+            include_str!("../fixtures/v2_timezone_name.json"),
+            Some(10),
+            -420,
+        );
+        let carol = guilds.scope_mut(Some(10)).user(3).unwrap();
+        assert_eq!(carol.timezone_name(), Some("America/Vancouver".to_string()));
+    }
+
+    #[test]
+    fn test_version_3() {
+        load_fixture(include_str!("../fixtures/v3_slots.json"), Some(10), -390);
+        let mut guilds = load_fixture(
+            include_str!("../fixtures/v3_overrides.json"),
+            Some(10),
+            -420,
+        );
+        let alice = guilds.scope_mut(Some(10)).user(1).unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 11, 2).unwrap();
+        assert!(!alice.is_available_on(date, 18 * 60, -420));
+        assert!(alice.is_available_on(date - Duration::days(7), 18 * 60, -420));
+
+        let at = Utc.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap();
+        let mut guilds = load_fixture(include_str!("../fixtures/v3_events.json"), Some(10), -420);
+        let events = guilds.scope_mut(Some(10)).events();
+        assert_eq!(events.upcoming(at).len(), 1);
+        assert_eq!(events.get(1).unwrap().rsvps(Rsvp::Maybe), vec![2]);
+
+        let mut guilds = load_fixture(
+            include_str!("../fixtures/v3_reminders.json"),
+            Some(10),
+            -420,
+        );
+        let events = guilds.scope_mut(Some(10)).events();
+        assert_eq!(events.upcoming(at).len(), 2);
+        assert_eq!(events.get(1).unwrap().reminder(), (None, false));
+        assert_eq!(events.get(2).unwrap().reminder(), (Some(30), true));
+        assert_eq!(events.get(2).unwrap().channel(), Some(50));
+    }
+
+    #[test]
+    fn test_version_4() {
+        let mut guilds = load_fixture(
+            include_str!("../fixtures/v4_account_names.json"),
+            Some(10),
+            -420,
+        );
+        let schedule = guilds.scope_mut(Some(10));
+        assert_eq!(schedule.find_id("captain"), Some(1));
+        assert_eq!(schedule.user(1).unwrap().name(), "Al");
+
+        // Guilds saved before they had a config use the defaults.
+        let mut guilds = load_fixture(
+            include_str!("../fixtures/v4_versioned.json"),
+            Some(10),
+            -420,
+        );
+        assert_eq!(guilds.scope_mut(Some(10)).config().prefix(), "?");
+    }
+
+    #[test]
+    fn test_version_5() {
+        let mut guilds = load_fixture(include_str!("../fixtures/v5_config.json"), Some(10), -420);
+        let config = guilds.scope_mut(Some(10)).config();
+        assert_eq!(config.prefix(), "!");
+        assert_eq!(config.expand("free mon"), "available mon");
+    }
+
+    #[test]
+    fn test_version_6() {
+        let mut guilds = load_fixture(include_str!("../fixtures/v6_periods.json"), Some(10), -420);
+        let config = guilds.scope_mut(Some(10)).config().clone();
+        assert_eq!(config.prefix(), "!");
+        assert_eq!(config.periods().get("raidtime"), Some(&(22 * 60, 2 * 60)));
+
+        // Periods are kept when the data is saved and loaded again.
+        let mut reloaded = parse(&to_string(&guilds).unwrap()).unwrap();
+        assert_eq!(reloaded.scope_mut(Some(10)).config(), &config);
+    }
+
+    #[test]
+    fn test_unreadable() {
+        assert!(parse("{}").is_err());
+        assert!(
+            parse(r#"{"version": 7, "guilds": {}, "direct_messages": {"users": {}}}"#).is_err()
+        );
+    }
+}
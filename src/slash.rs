@@ -0,0 +1,590 @@
+//===----------------------------------------------------------------------===//
+// slash.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::bot::Bot;
+use crate::chat::{Author, IncomingMessage};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// The Discord API, which slash commands are registered with and answered through.
+pub const API_URL: &str = "https://discord.com/api/v10";
+
+/// The types of interactions, and of the responses to them.
+const APPLICATION_COMMAND: u8 = 2;
+const APPLICATION_COMMAND_AUTOCOMPLETE: u8 = 4;
+const CHANNEL_MESSAGE_WITH_SOURCE: u8 = 4;
+const APPLICATION_COMMAND_AUTOCOMPLETE_RESULT: u8 = 8;
+
+/// The type of a slash command, and of its options.
+const CHAT_INPUT: u8 = 1;
+const STRING: u8 = 3;
+const USER: u8 = 6;
+
+/// The permissions which let a member change the config of the guild.
+const ADMINISTRATOR: u64 = 1 << 3;
+const MANAGE_GUILD: u64 = 1 << 5;
+
+/// The most suggestions Discord shows while an option is typed.
+const MAX_CHOICES: usize = 25;
+
+/// The words suggested while days are typed.
+const DAY_WORDS: [&str; 9] = [
+    "mon", "tue", "wed", "thu", "fri", "sat", "sun", "weekdays", "weekends",
+];
+
+/// The reply to a slash command which succeeded without anything to show,
+/// since Discord expects every interaction to be answered.
+const DONE: &str = "Done!";
+
+/// How the value of an option is passed to the query.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum OptionKind {
+    /// Text which is parsed word by word, eg. mon wed.
+    Words,
+    /// Text which is kept as a single param, eg. a name with spaces.
+    /// It cannot contain quotes, since they would end the param.
+    Quoted,
+    /// A user picked from the members of the guild, passed as their id.
+    User,
+}
+
+impl OptionKind {
+    /// The type of the option, as registered with Discord.
+    fn option_type(self) -> u8 {
+        match self {
+            OptionKind::Words | OptionKind::Quoted => STRING,
+            OptionKind::User => USER,
+        }
+    }
+}
+
+/// What is suggested while an option is typed.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Completion {
+    Nothing,
+    Days,
+    Users,
+}
+
+/// An option of a slash command.
+/// The values of the options are passed to the query in the order they are declared,
+/// so that they are parsed the same way as the text command.
+struct CommandOption {
+    name: &'static str,
+    description: &'static str,
+    required: bool,
+    completion: Completion,
+    kind: OptionKind,
+}
+
+/// A slash command, which has the same name as the text command it runs.
+struct Command {
+    name: &'static str,
+    description: &'static str,
+    options: &'static [CommandOption],
+}
+
+const DAYS_OPTION: CommandOption = CommandOption {
+    name: "days",
+    description: "The days, eg. mon wed, weekends or from mon to fri",
+    required: true,
+    completion: Completion::Days,
+    kind: OptionKind::Words,
+};
+
+const TIMES_OPTION: CommandOption = CommandOption {
+    name: "times",
+    description: "The times, eg. 18 19, from 18 to 23 or 7pm-11pm",
+    required: true,
+    completion: Completion::Nothing,
+    kind: OptionKind::Words,
+};
+
+const COMMANDS: [Command; 6] = [
+    Command {
+        name: "add",
+        description: "Add days and times to your schedule",
+        options: &[DAYS_OPTION, TIMES_OPTION],
+    },
+    Command {
+        name: "remove",
+        description: "Remove days and times from your schedule",
+        options: &[DAYS_OPTION, TIMES_OPTION],
+    },
+    Command {
+        name: "view",
+        description: "View the schedule of a user, or your own",
+        options: &[
+            CommandOption {
+                name: "user",
+                description: "The name of the user, eg. alice",
+                required: false,
+                completion: Completion::Users,
+                kind: OptionKind::Quoted,
+            },
+            CommandOption {
+                name: "member",
+                description: "Or the member, eg. @alice",
+                required: false,
+                completion: Completion::Nothing,
+                kind: OptionKind::User,
+            },
+        ],
+    },
+    Command {
+        name: "available",
+        description: "See who is available on a day or date",
+        options: &[
+            CommandOption {
+                name: "day",
+                description: "The day or date, eg. mon, tomorrow or 2026-11-03",
+                required: true,
+                completion: Completion::Days,
+                kind: OptionKind::Words,
+            },
+            CommandOption {
+                name: "time",
+                description: "The time, eg. 18, 18:30 or 7pm",
+                required: false,
+                completion: Completion::Nothing,
+                kind: OptionKind::Words,
+            },
+        ],
+    },
+    Command {
+        name: "timezone",
+        description: "Set your timezone, or view it",
+        options: &[CommandOption {
+            name: "timezone",
+            description: "The timezone, eg. -7, +5:30 or America/Vancouver",
+            required: false,
+            completion: Completion::Nothing,
+            kind: OptionKind::Words,
+        }],
+    },
+    Command {
+        name: "name",
+        description: "Set your name, or view it",
+        options: &[CommandOption {
+            name: "name",
+            description: "The name, eg. philio",
+            required: false,
+            completion: Completion::Nothing,
+            kind: OptionKind::Words,
+        }],
+    },
+];
+
+/// Returns the definitions of every slash command,
+/// as they are registered with Discord.
+pub fn commands() -> Value {
+    COMMANDS
+        .iter()
+        .map(|command| {
+            json!({
+                "name": command.name,
+                "type": CHAT_INPUT,
+                "description": command.description,
+                "options": command.options.iter().map(|option| json!({
+                    "name": option.name,
+                    "type": option.kind.option_type(),
+                    "description": option.description,
+                    "required": option.required,
+                    "autocomplete": option.completion != Completion::Nothing,
+                })).collect::<Vec<Value>>(),
+            })
+        })
+        .collect()
+}
+
+/// Where the slash commands of the application are registered.
+pub fn commands_url(application_id: u64) -> String {
+    API_URL.to_string() + "/applications/" + &application_id.to_string() + "/commands"
+}
+
+/// A slash command being run, or one of its options being typed, as sent by Discord.
+/// Only the fields the bot uses are kept.
+#[derive(Deserialize, Debug)]
+pub struct Interaction {
+    id: String,
+    token: String,
+    #[serde(rename = "type")]
+    kind: u8,
+    #[serde(default)]
+    data: Option<InteractionData>,
+    #[serde(default)]
+    guild_id: Option<String>,
+    #[serde(default)]
+    channel_id: Option<String>,
+    /// Who ran the command, in a guild.
+    #[serde(default)]
+    member: Option<Member>,
+    /// Who ran the command, in direct messages.
+    #[serde(default)]
+    user: Option<DiscordUser>,
+}
+
+#[derive(Deserialize, Debug)]
+struct InteractionData {
+    name: String,
+    #[serde(default)]
+    options: Vec<InteractionOption>,
+}
+
+#[derive(Deserialize, Debug)]
+struct InteractionOption {
+    name: String,
+    value: Value,
+    /// Whether the option is being typed, when autocompleting.
+    #[serde(default)]
+    focused: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Member {
+    user: DiscordUser,
+    #[serde(default)]
+    nick: Option<String>,
+    /// The permissions of the member in the channel, as a bit set.
+    #[serde(default)]
+    permissions: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DiscordUser {
+    id: String,
+    username: String,
+}
+
+impl Interaction {
+    /// Reads an interaction from the payload of an INTERACTION_CREATE event.
+    pub fn from_value(payload: Value) -> Result<Interaction, String> {
+        serde_json::from_value(payload).map_err(|why| why.to_string())
+    }
+
+    /// Where the response to the interaction is sent.
+    pub fn callback_url(&self) -> String {
+        API_URL.to_string() + "/interactions/" + &self.id + "/" + &self.token + "/callback"
+    }
+
+    /// The guild the command was run in, or None in direct messages.
+    fn guild_id(&self) -> Option<u64> {
+        self.guild_id.as_ref().and_then(|id| id.parse().ok())
+    }
+
+    fn author(&self) -> Option<Author> {
+        let (user, nickname) = match (&self.member, &self.user) {
+            (Some(member), _) => (&member.user, member.nick.clone()),
+            (None, Some(user)) => (user, None),
+            (None, None) => return None,
+        };
+        Some(Author {
+            id: user.id.parse().ok()?,
+            name: user.username.clone(),
+            nickname,
+        })
+    }
+
+    /// Whether the member who ran the command may manage the guild.
+    fn manages_guild(&self) -> bool {
+        self.member
+            .as_ref()
+            .and_then(|member| member.permissions.as_ref())
+            .and_then(|permissions| permissions.parse::<u64>().ok())
+            .is_some_and(|permissions| permissions & (ADMINISTRATOR | MANAGE_GUILD) != 0)
+    }
+
+    fn command(&self) -> Option<(&'static Command, &InteractionData)> {
+        let data = self.data.as_ref()?;
+        COMMANDS
+            .iter()
+            .find(|command| command.name == data.name)
+            .map(|command| (command, data))
+    }
+
+    /// Turns the slash command into the text of the same query,
+    /// eg. /add days:mon times:18 19 into "add mon 18 19".
+    pub fn to_message(&self) -> Option<IncomingMessage> {
+        let (command, data) = self.command()?;
+        let content = command
+            .options
+            .iter()
+            .filter_map(|option| {
+                let value = data.options.iter().find(|val| val.name == option.name)?;
+                let value = option_text(&value.value);
+                match option.kind {
+                    OptionKind::Words => Some(value),
+                    OptionKind::Quoted => Some("\"".to_string() + &value + "\""),
+                    OptionKind::User => Some("<@".to_string() + &value + ">"),
+                }
+            })
+            .fold(command.name.to_string(), |query, value| {
+                query + " " + &value
+            });
+
+        Some(IncomingMessage {
+            author: self.author()?,
+            guild_id: self.guild_id(),
+            channel_id: self.channel_id.as_ref()?.parse().ok()?,
+            manages_guild: self.manages_guild(),
+            content,
+        })
+    }
+
+    /// Returns why the options cannot be turned into a query, if they cannot,
+    /// eg. a quote in a name, which would end it early.
+    fn invalid_option(&self) -> Option<String> {
+        let (command, data) = self.command()?;
+        command
+            .options
+            .iter()
+            .filter(|option| option.kind == OptionKind::Quoted)
+            .find(|option| {
+                data.options.iter().any(|val| {
+                    val.name == option.name && option_text(&val.value).contains(['"', '“', '”'])
+                })
+            })
+            .map(|option| "The ".to_string() + option.name + " cannot contain quotes")
+    }
+
+    /// Returns suggestions for the option being typed, which replace its value.
+    /// Users are suggested from the names given.
+    fn complete(&self, user_names: &[String]) -> Vec<String> {
+        let (command, data) = match self.command() {
+            Some(command) => command,
+            None => return vec![],
+        };
+        let typed = match data.options.iter().find(|option| option.focused) {
+            Some(typed) => typed,
+            None => return vec![],
+        };
+        let completion = command
+            .options
+            .iter()
+            .find(|option| option.name == typed.name)
+            .map_or(Completion::Nothing, |option| option.completion);
+        let text = option_text(&typed.value);
+
+        match completion {
+            Completion::Nothing => vec![],
+            Completion::Days => complete_days(&text),
+            Completion::Users => user_names
+                .iter()
+                .filter(|name| name.to_lowercase().contains(&text.trim().to_lowercase()))
+                .cloned()
+                .collect(),
+        }
+        .into_iter()
+        .take(MAX_CHOICES)
+        .collect()
+    }
+}
+
+/// The value of an option as text, whatever its type.
+fn option_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Suggests days to complete the last word being typed, keeping the ones before it,
+/// eg. "mon w" suggests "mon wed", "mon weekdays" and "mon weekends".
+fn complete_days(text: &str) -> Vec<String> {
+    let (before, word) = match text.rfind(' ') {
+        Some(idx) => (&text[..=idx], text[idx + 1..].to_lowercase()),
+        None => ("", text.to_lowercase()),
+    };
+    DAY_WORDS
+        .iter()
+        .filter(|day| day.starts_with(&word))
+        .map(|day| before.to_string() + day)
+        .collect()
+}
+
+/// Handles an interaction, and returns the response to send back to Discord.
+/// Slash commands are handled by the bot the same way as text commands.
+pub fn respond(bot: &Bot, interaction: &Interaction) -> Option<Value> {
+    match interaction.kind {
+        APPLICATION_COMMAND => {
+            let content = match interaction.invalid_option() {
+                Some(why) => why,
+                None => bot
+                    .handle_command(&interaction.to_message()?)
+                    .unwrap_or_else(|| DONE.to_string()),
+            };
+            Some(json!({
+                "type": CHANNEL_MESSAGE_WITH_SOURCE,
+                "data": { "content": content },
+            }))
+        }
+        APPLICATION_COMMAND_AUTOCOMPLETE => {
+            let choices = interaction
+                .complete(&bot.user_names(interaction.guild_id()))
+                .into_iter()
+                .map(|choice| json!({ "name": choice, "value": choice }))
+                .collect::<Vec<Value>>();
+            Some(json!({
+                "type": APPLICATION_COMMAND_AUTOCOMPLETE_RESULT,
+                "data": { "choices": choices },
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guilds::GuildCollection;
+    use crate::storage::MemoryStorage;
+    use std::sync::Arc;
+
+    fn interaction(payload: &str) -> Interaction {
+        Interaction::from_value(serde_json::from_str(payload).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_commands() {
+        let commands = commands();
+        let names = commands
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|command| command["name"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            names,
+            vec!["add", "remove", "view", "available", "timezone", "name"]
+        );
+        assert_eq!(commands[0]["options"][0]["name"], "days");
+        assert_eq!(commands[0]["options"][0]["autocomplete"], true);
+        assert_eq!(commands[0]["options"][1]["autocomplete"], false);
+        assert_eq!(commands[2]["options"][0]["required"], false);
+        assert_eq!(commands[2]["options"][0]["type"], STRING);
+        assert_eq!(commands[2]["options"][1]["type"], USER);
+        assert_eq!(commands[2]["options"][1]["autocomplete"], false);
+    }
+
+    #[test]
+    fn test_to_message() {
+        let add = interaction(include_str!("../fixtures/interaction_add.json"));
+        assert_eq!(
+            add.to_message(),
+            Some(IncomingMessage {
+                author: Author {
+                    id: 200,
+                    name: "alice".to_string(),
+                    nickname: Some("Al".to_string()),
+                },
+                guild_id: Some(10),
+                channel_id: 50,
+                manages_guild: true,
+                content: "add mon wed from 18 to 23".to_string(),
+            })
+        );
+        assert_eq!(
+            add.callback_url(),
+            "https://discord.com/api/v10/interactions/900/aW50ZXJhY3Rpb24/callback"
+        );
+
+        let view = interaction(include_str!("../fixtures/interaction_view_dm.json"));
+        assert_eq!(
+            view.to_message(),
+            Some(IncomingMessage {
+                author: Author {
+                    id: 300,
+                    name: "bob".to_string(),
+                    nickname: None,
+                },
+                guild_id: None,
+                channel_id: 60,
+                manages_guild: false,
+                content: "view \"Captain Al\"".to_string(),
+            })
+        );
+
+        let member = interaction(include_str!("../fixtures/interaction_view_member.json"));
+        assert_eq!(
+            member.to_message().map(|message| message.content),
+            Some("view <@200>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let days = interaction(include_str!(
+            "../fixtures/interaction_autocomplete_days.json"
+        ));
+        assert_eq!(
+            days.complete(&[]),
+            vec!["mon wed", "mon weekdays", "mon weekends"]
+        );
+        assert_eq!(complete_days("").len(), DAY_WORDS.len());
+        assert_eq!(complete_days("Sa"), vec!["sat"]);
+
+        let users = interaction(include_str!(
+            "../fixtures/interaction_autocomplete_users.json"
+        ));
+        assert_eq!(
+            users.complete(&[
+                "alice".to_string(),
+                "bob".to_string(),
+                "captain al".to_string()
+            ]),
+            vec!["alice", "captain al"]
+        );
+    }
+
+    #[test]
+    fn test_respond() {
+        let storage = Arc::new(MemoryStorage::default());
+        let bot = Bot::new(GuildCollection::new(), storage);
+
+        let add = interaction(include_str!("../fixtures/interaction_add.json"));
+        assert_eq!(
+            respond(&bot, &add),
+            Some(json!({ "type": 4, "data": { "content": "Done!" } }))
+        );
+        assert_eq!(bot.user_names(Some(10)), vec!["alice"]);
+        assert!(bot.user_names(None).is_empty());
+
+        let users = interaction(include_str!(
+            "../fixtures/interaction_autocomplete_users.json"
+        ));
+        assert_eq!(
+            respond(&bot, &users),
+            Some(json!({
+                "type": 8,
+                "data": { "choices": [{ "name": "alice", "value": "alice" }] },
+            }))
+        );
+
+        // The reply is the same as for the text command.
+        let view = interaction(include_str!("../fixtures/interaction_view_dm.json"));
+        let reply = respond(&bot, &view).unwrap();
+        assert_eq!(
+            reply["data"]["content"],
+            "Could not find the user \"Captain Al\"\n".to_string()
+                + "Usage: ?view <user>, eg. ?view @alice or ?view alice"
+        );
+        let member = interaction(include_str!("../fixtures/interaction_view_member.json"));
+        let reply = respond(&bot, &member).unwrap();
+        assert!(reply["data"]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("```"));
+
+        // A quote would end the name early, so it is refused.
+        let quote = interaction(include_str!("../fixtures/interaction_view_quote.json"));
+        assert_eq!(
+            respond(&bot, &quote),
+            Some(json!({ "type": 4, "data": { "content": "The user cannot contain quotes" } }))
+        );
+    }
+}
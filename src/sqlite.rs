@@ -0,0 +1,417 @@
+//===----------------------------------------------------------------------===//
+// sqlite.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::config::GuildConfig;
+use crate::event::EventCollection;
+use crate::guilds::GuildCollection;
+use crate::schedules::ScheduleCollection;
+use crate::schema::{self, StoredCollection, StoredData};
+use crate::storage::{self, LoadError, Storage};
+use rusqlite::{params, types::Type, Connection, Transaction};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The name of the direct message scope, and of the schedules from before
+/// data was isolated by guild, which have not been moved into a guild yet.
+const DIRECT_MESSAGES: &str = "direct_messages";
+const LEGACY: &str = "legacy";
+
+/// The version of the data format in databases which were created
+/// before the version was saved in them.
+const UNVERSIONED: u32 = 4;
+
+/// Keeps the data in an embedded SQLite database.
+/// Every scope (guild) has its own table of users, with a row per user,
+/// so that handling a message only writes the users of that guild which changed.
+/// The events and config of each scope are kept in the scopes table.
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens the database, creating it if it does not exist.
+    pub fn open(path: &Path) -> rusqlite::Result<SqliteStorage> {
+        SqliteStorage::with_connection(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> rusqlite::Result<SqliteStorage> {
+        SqliteStorage::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> rusqlite::Result<SqliteStorage> {
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS scopes (
+                 name TEXT PRIMARY KEY,
+                 events TEXT NOT NULL,
+                 config TEXT NOT NULL DEFAULT '{}'
+             );",
+        )?;
+        // Databases created before guilds had a config are given the default one.
+        let has_config: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('scopes') WHERE name = 'config'",
+            [],
+            |row| row.get(0),
+        )?;
+        if has_config == 0 {
+            connection.execute_batch(
+                "ALTER TABLE scopes ADD COLUMN config TEXT NOT NULL DEFAULT '{}';",
+            )?;
+        }
+        if count_scopes(&connection)? == 0 {
+            connection.pragma_update(None, "user_version", schema::VERSION)?;
+        }
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Copies the data from a JSON data file (or its latest readable backup)
+    /// into the database. Refuses to import into a database which already
+    /// contains data, so that it is never overwritten.
+    pub fn import_json(&self, path: &Path) -> Result<(), String> {
+        if !path.exists() {
+            return Err(format!("Could not find {}", path.display()));
+        }
+        let guilds = storage::load(path).map_err(|why| why.to_string())?;
+        let scopes =
+            count_scopes(&self.connection.lock().unwrap()).map_err(|why| why.to_string())?;
+        if scopes > 0 {
+            return Err("The database already contains data".to_string());
+        }
+        self.save(&guilds)
+    }
+
+    /// Makes the changes in a single transaction, so that nothing is written
+    /// if any of them fail.
+    fn write<F>(&self, changes: F) -> Result<(), String>
+    where
+        F: FnOnce(&Transaction) -> rusqlite::Result<()>,
+    {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(|why| why.to_string())?;
+        changes(&transaction).map_err(|why| why.to_string())?;
+        transaction.commit().map_err(|why| why.to_string())
+    }
+}
+
+impl Storage for SqliteStorage {
+    /// Older databases are upgraded to the current version of the format,
+    /// and saved again so that they are only upgraded once.
+    fn load(&self) -> Result<GuildCollection, LoadError> {
+        let (version, data) = read_data(&self.connection.lock().unwrap())
+            .map_err(|why| LoadError::Database(why.to_string()))?;
+        let guilds = schema::migrate(data).map_err(LoadError::Database)?;
+        if version != Some(schema::VERSION) {
+            self.save(&guilds).map_err(LoadError::Database)?;
+        }
+        Ok(guilds)
+    }
+
+    fn save(&self, guilds: &GuildCollection) -> Result<(), String> {
+        self.write(|transaction| {
+            match guilds.legacy() {
+                Some(legacy) => write_scope(transaction, LEGACY, legacy)?,
+                None => delete_scope(transaction, LEGACY)?,
+            }
+            for (guild_id, schedule) in guilds.scopes() {
+                write_scope(transaction, &scope_name(guild_id), schedule)?;
+            }
+            transaction.pragma_update(None, "user_version", schema::VERSION)
+        })
+    }
+
+    fn save_scope(&self, guilds: &GuildCollection, guild_id: Option<u64>) -> Result<(), String> {
+        match guilds.scope(guild_id) {
+            Some(schedule) => {
+                self.write(|transaction| write_scope(transaction, &scope_name(guild_id), schedule))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the name of a guild's scope, eg. guild_1234.
+fn scope_name(guild_id: Option<u64>) -> String {
+    match guild_id {
+        Some(id) => format!("guild_{}", id),
+        None => DIRECT_MESSAGES.to_string(),
+    }
+}
+
+/// Returns the name of the table containing the users of a scope.
+/// Scope names only ever contain letters, digits and underscores.
+fn users_table(scope: &str) -> String {
+    format!("\"{}_users\"", scope)
+}
+
+/// Writes a scope, only updating the rows of users which changed.
+fn write_scope(
+    transaction: &Transaction,
+    scope: &str,
+    schedule: &ScheduleCollection,
+) -> rusqlite::Result<()> {
+    let table = users_table(scope);
+    transaction.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, user TEXT NOT NULL);",
+        table
+    ))?;
+    transaction.execute(
+        "INSERT INTO scopes (name, events, config) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET events = excluded.events, config = excluded.config
+         WHERE events != excluded.events OR config != excluded.config",
+        params![
+            scope,
+            to_json(schedule.events())?,
+            to_json(schedule.config())?
+        ],
+    )?;
+
+    let mut statement = transaction.prepare(&format!(
+        "INSERT INTO {} (id, user) VALUES (?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET user = excluded.user
+         WHERE user != excluded.user",
+        table
+    ))?;
+    for (id, usr) in schedule.users() {
+        // Ids are stored with the same bits, since SQLite integers are signed.
+        statement.execute(params![*id as i64, to_json(usr)?])?;
+    }
+    Ok(())
+}
+
+/// Removes a scope, along with its users, if it exists.
+fn delete_scope(transaction: &Transaction, scope: &str) -> rusqlite::Result<()> {
+    transaction.execute("DELETE FROM scopes WHERE name = ?1", params![scope])?;
+    transaction.execute_batch(&format!("DROP TABLE IF EXISTS {};", users_table(scope)))
+}
+
+/// Returns the number of scopes in the database.
+fn count_scopes(connection: &Connection) -> rusqlite::Result<i64> {
+    connection.query_row("SELECT COUNT(*) FROM scopes", [], |row| row.get(0))
+}
+
+/// Reads every scope in the database, along with the version of their format,
+/// which is None if the database was created before the version was saved.
+fn read_data(connection: &Connection) -> rusqlite::Result<(Option<u32>, StoredData)> {
+    let version = match connection.query_row("PRAGMA user_version", [], |row| row.get(0))? {
+        0 => None,
+        version => Some(version),
+    };
+    let scopes = connection
+        .prepare("SELECT name, events, config FROM scopes")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<(String, String, String)>>>()?;
+
+    let mut guilds = HashMap::new();
+    let mut direct_messages = StoredCollection::new(
+        HashMap::new(),
+        EventCollection::new(),
+        GuildConfig::default(),
+    );
+    let mut legacy = None;
+    for (name, events, config) in scopes {
+        let schedule = read_scope(connection, &name, &events, &config)?;
+        match name.as_str() {
+            DIRECT_MESSAGES => direct_messages = schedule,
+            LEGACY => legacy = Some(schedule),
+            _ => match name
+                .strip_prefix("guild_")
+                .and_then(|id| id.parse::<u64>().ok())
+            {
+                Some(id) => {
+                    guilds.insert(id, schedule);
+                }
+                None => {
+                    return Err(rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        Type::Text,
+                        format!("Unknown scope \"{}\"", name).into(),
+                    ))
+                }
+            },
+        }
+    }
+    let data = StoredData::from_scopes(
+        version.unwrap_or(UNVERSIONED),
+        guilds,
+        direct_messages,
+        legacy,
+    );
+    Ok((version, data))
+}
+
+/// Reads the users of a scope, along with its events and config.
+fn read_scope(
+    connection: &Connection,
+    scope: &str,
+    events: &str,
+    config: &str,
+) -> rusqlite::Result<StoredCollection> {
+    let users = connection
+        .prepare(&format!("SELECT id, user FROM {}", users_table(scope)))?
+        .query_map([], |row| {
+            let user = row.get::<_, String>(1)?;
+            Ok((row.get::<_, i64>(0)? as u64, from_json(1, &user)?))
+        })?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(StoredCollection::new(
+        users,
+        from_json(1, events)?,
+        from_json(2, config)?,
+    ))
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|why| rusqlite::Error::ToSqlConversionFailure(why.into()))
+}
+
+fn from_json<T: DeserializeOwned>(column: usize, serialized: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(serialized)
+        .map_err(|why| rusqlite::Error::FromSqlConversionFailure(column, Type::Text, why.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::day::Day;
+    use std::fs;
+
+    #[test]
+    fn test_save_and_load() {
+        let database = SqliteStorage::open_in_memory().unwrap();
+        assert!(database.load().unwrap().scope(Some(1)).is_none());
+
+        let mut legacy = ScheduleCollection::new();
+        legacy.insert_user(1, "alice");
+        let mut guilds = GuildCollection::from_legacy(legacy);
+        guilds.scope_mut(Some(10)).insert_user(2, "bob");
+        database.save(&guilds).unwrap();
+
+        // Only the scope which changed is saved.
+        let schedule = guilds.scope_mut(Some(10));
+        schedule
+            .mut_user(2)
+            .unwrap()
+            .set_time(Day::Mon, 18 * 60, true);
+        guilds.scope_mut(None).insert_user(3, "carol");
+        database.save_scope(&guilds, Some(10)).unwrap();
+
+        let mut loaded = database.load().unwrap();
+        assert!(loaded.scope(None).unwrap().user(3).is_none());
+        assert!(loaded.legacy().unwrap().user(1).is_some());
+        let schedule = loaded.scope(Some(10)).unwrap();
+        assert!(schedule.user(1).is_none());
+        assert_eq!(
+            schedule.user(2).unwrap().get_raw_schedule(),
+            guilds
+                .scope(Some(10))
+                .unwrap()
+                .user(2)
+                .unwrap()
+                .get_raw_schedule()
+        );
+        assert!(loaded.scope_mut(Some(20)).user(1).is_none());
+
+        // Once the old schedules are moved into a guild, they are no longer kept apart.
+        loaded.move_legacy(10).unwrap();
+        database.save(&loaded).unwrap();
+        let loaded = database.load().unwrap();
+        assert!(loaded.legacy().is_none());
+        assert!(loaded.scope(Some(10)).unwrap().user(1).is_some());
+    }
+
+    #[test]
+    fn test_version() {
+        let database = SqliteStorage::open_in_memory().unwrap();
+        let mut guilds = GuildCollection::new();
+        guilds.scope_mut(Some(10)).insert_user(1, "alice");
+        database.save(&guilds).unwrap();
+
+        // Databases from before the version was saved are upgraded and saved again.
+        let set_version = |version: u32| {
+            let connection = database.connection.lock().unwrap();
+            connection
+                .pragma_update(None, "user_version", version)
+                .unwrap();
+        };
+        set_version(0);
+        assert!(database.load().unwrap().scope(Some(10)).is_some());
+        let connection = database.connection.lock().unwrap();
+        let version: u32 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, schema::VERSION);
+        drop(connection);
+
+        // Data from a newer version of the bot is never read.
+        set_version(schema::VERSION + 1);
+        assert!(database.load().is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        // A database from before guilds had a config.
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE scopes (name TEXT PRIMARY KEY, events TEXT NOT NULL);
+                 CREATE TABLE \"guild_10_users\" (id INTEGER PRIMARY KEY, user TEXT NOT NULL);
+                 INSERT INTO scopes VALUES ('guild_10', '{\"events\": {}, \"next_id\": 1}');
+                 PRAGMA user_version = 4;",
+            )
+            .unwrap();
+        let database = SqliteStorage::with_connection(connection).unwrap();
+        let mut guilds = database.load().unwrap();
+        assert_eq!(guilds.scope(Some(10)).unwrap().config().prefix(), "?");
+
+        guilds
+            .scope_mut(Some(10))
+            .config_mut()
+            .set_prefix("!".to_string());
+        database.save_scope(&guilds, Some(10)).unwrap();
+        let guilds = database.load().unwrap();
+        assert_eq!(guilds.scope(Some(10)).unwrap().config().prefix(), "!");
+    }
+
+    #[test]
+    fn test_import_json() {
+        let dir = std::env::temp_dir().join(format!("scheduler_bot_import_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.json");
+
+        let database = SqliteStorage::open_in_memory().unwrap();
+        assert!(database.import_json(&path).is_err());
+
+        let mut guilds = GuildCollection::new();
+        guilds.scope_mut(Some(10)).insert_user(1, "alice");
+        storage::save(&path, &guilds).unwrap();
+        database.import_json(&path).unwrap();
+        assert!(database
+            .load()
+            .unwrap()
+            .scope(Some(10))
+            .unwrap()
+            .user(1)
+            .is_some());
+
+        // Importing twice would overwrite changes made since.
+        assert!(database.import_json(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+//===----------------------------------------------------------------------===//
+// storage.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use crate::guilds::GuildCollection;
+use crate::schema;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The number of previous versions of the data file which are kept as backups,
+/// eg. data.json.1 is the version before the latest save.
+pub const BACKUPS: u32 = 5;
+
+/// Where the data is kept between runs of the bot.
+/// Implementations must be safe to share with the reminder scheduler.
+pub trait Storage: Send + Sync {
+    /// Loads every guild. New data is only returned if there was no data at all,
+    /// so that data is never discarded because it could not be read.
+    fn load(&self) -> Result<GuildCollection, LoadError>;
+
+    /// Saves every guild.
+    fn save(&self, guilds: &GuildCollection) -> Result<(), String>;
+
+    /// Saves the schedules of a single guild, or the direct message scope,
+    /// after a message sent there was handled.
+    fn save_scope(&self, guilds: &GuildCollection, guild_id: Option<u64>) -> Result<(), String>;
+}
+
+/// Keeps every guild in a single JSON file, along with backups of it.
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(path: &Path) -> JsonStorage {
+        JsonStorage {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<GuildCollection, LoadError> {
+        load(&self.path)
+    }
+
+    fn save(&self, guilds: &GuildCollection) -> Result<(), String> {
+        save(&self.path, guilds).map_err(|why| why.to_string())
+    }
+
+    /// The whole file is rewritten, since it cannot be updated in place.
+    fn save_scope(&self, guilds: &GuildCollection, _guild_id: Option<u64>) -> Result<(), String> {
+        self.save(guilds)
+    }
+}
+
+/// Keeps the data in memory, serialized as it would be in the data file.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub saved: std::sync::Mutex<Option<String>>,
+}
+
+#[cfg(test)]
+impl Storage for MemoryStorage {
+    fn load(&self) -> Result<GuildCollection, LoadError> {
+        match self.saved.lock().unwrap().as_deref() {
+            Some(serialized) => parse_data(serialized)
+                .map_err(|why| LoadError::Unreadable(vec![(PathBuf::new(), why)])),
+            None => Ok(GuildCollection::new()),
+        }
+    }
+
+    fn save(&self, guilds: &GuildCollection) -> Result<(), String> {
+        *self.saved.lock().unwrap() =
+            Some(schema::to_string(guilds).map_err(|why| why.to_string())?);
+        Ok(())
+    }
+
+    fn save_scope(&self, guilds: &GuildCollection, _guild_id: Option<u64>) -> Result<(), String> {
+        self.save(guilds)
+    }
+}
+
+/// Reasons the data could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The data file or a backup exists, but none of them could be parsed.
+    /// The bot should not start, since it would overwrite them.
+    Unreadable(Vec<(PathBuf, String)>),
+    /// The database could not be opened or read.
+    Database(String),
+}
+
+/// Lists every file which could not be read, and why.
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Unreadable(failures) => {
+                for (path, why) in failures {
+                    writeln!(f, "{}: {}", path.display(), why)?;
+                }
+                Ok(())
+            }
+            LoadError::Database(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+/// Returns the path of a backup of the data file, eg. data.json.2.
+pub fn backup_path(path: &Path, generation: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(generation.to_string());
+    PathBuf::from(name)
+}
+
+/// Returns the path of the file which is written before replacing the data file.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Parses the contents of a data file, saved by any version of the bot.
+pub fn parse_data(serialized: &str) -> Result<GuildCollection, String> {
+    schema::parse(serialized)
+}
+
+/// Loads the data file, or the most recent backup which can be parsed if it cannot.
+/// A new collection is only created if neither the data file nor any backup exist,
+/// so that data is never discarded because of a parse error.
+pub fn load(path: &Path) -> Result<GuildCollection, LoadError> {
+    let mut failures = vec![];
+    let candidates =
+        std::iter::once(path.to_path_buf()).chain((1..=BACKUPS).map(|gen| backup_path(path, gen)));
+    for candidate in candidates {
+        let serialized = match fs::read_to_string(&candidate) {
+            Ok(serialized) => serialized,
+            Err(why) if why.kind() == io::ErrorKind::NotFound => continue,
+            Err(why) => {
+                failures.push((candidate, why.to_string()));
+                continue;
+            }
+        };
+        match parse_data(&serialized) {
+            Ok(guilds) => {
+                if !failures.is_empty() {
+                    eprintln!("Loaded backup {:?} after errors: {:?}", candidate, failures);
+                }
+                return Ok(guilds);
+            }
+            Err(why) => failures.push((candidate, why)),
+        }
+    }
+
+    if failures.is_empty() {
+        eprintln!("Could not find data file. Creating new...");
+        Ok(GuildCollection::new())
+    } else {
+        Err(LoadError::Unreadable(failures))
+    }
+}
+
+/// Saves the data without ever leaving a partially written data file.
+/// The data is written to a temporary file which then replaces the data file,
+/// and the previous version is kept as a backup.
+pub fn save(path: &Path, guilds: &GuildCollection) -> io::Result<()> {
+    let serialized = schema::to_string(guilds)?;
+    let temp = temp_path(path);
+    {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()?;
+    }
+
+    if path.exists() {
+        for gen in (1..BACKUPS).rev() {
+            let backup = backup_path(path, gen);
+            if backup.exists() {
+                fs::rename(&backup, backup_path(path, gen + 1))?;
+            }
+        }
+        fs::copy(path, backup_path(path, 1))?;
+    }
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an empty directory for the test to keep its files in.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("scheduler_bot_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = test_dir("storage");
+        let path = dir.join("data.json");
+        assert!(load(&path).is_ok());
+
+        for id in 1..=(BACKUPS as u64 + 2) {
+            let mut guilds = GuildCollection::new();
+            guilds.scope_mut(Some(1)).insert_user(id, "alice");
+            save(&path, &guilds).unwrap();
+        }
+        assert!(!temp_path(&path).exists());
+        assert!(backup_path(&path, BACKUPS).exists());
+        assert!(!backup_path(&path, BACKUPS + 1).exists());
+        let mut guilds = load(&path).unwrap();
+        assert!(guilds.scope_mut(Some(1)).user(BACKUPS as u64 + 2).is_some());
+
+        // A corrupted data file falls back to the latest backup.
+        fs::write(&path, "{\"guilds\": {").unwrap();
+        let mut guilds = load(&path).unwrap();
+        assert!(guilds.scope_mut(Some(1)).user(BACKUPS as u64 + 1).is_some());
+
+        // If nothing can be read, nothing is discarded.
+        for gen in 1..=BACKUPS {
+            fs::write(backup_path(&path, gen), "").unwrap();
+        }
+        match load(&path) {
+            Err(LoadError::Unreadable(failures)) => {
+                assert_eq!(failures.len(), BACKUPS as usize + 1)
+            }
+            _ => panic!("Loaded unreadable data"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_legacy() {
+        let dir = test_dir("legacy");
+        let path = dir.join("data.json");
+        fs::write(
+            &path,
+            r#"{"users": {"1": {"name": "alice", "timezone": 0, "schedule": [0, 0, 0, 0, 0, 0, 0]}},
+                "name_id_map": {"alice": 1}}"#,
+        )
+        .unwrap();
+        let mut guilds = load(&path).unwrap();
+        assert!(guilds.legacy().unwrap().user(1).is_some());
+        assert!(guilds.scope_mut(Some(10)).user(1).is_none());
+        assert!(guilds.scope_mut(None).user(1).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//===----------------------------------------------------------------------===//
+// timezone.rs
+//
+// This source file is part of the scheduler_bot project
+//
+// Copyright (c) 2020 Philippe Nadon
+// Licensed under Apache License v2.0
+//===----------------------------------------------------------------------===//
+use chrono::{DateTime, Offset, TimeZone, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+/// Common timezone abbreviations, and the region they are most likely to mean.
+/// Regions are used instead of fixed offsets so that daylight saving time
+/// is followed, eg. someone who types "pst" in the summer means "pdt".
+static ABBREVIATIONS: &[(&str, &str)] = &[
+    ("pst", "America/Los_Angeles"),
+    ("pdt", "America/Los_Angeles"),
+    ("pt", "America/Los_Angeles"),
+    ("mst", "America/Denver"),
+    ("mdt", "America/Denver"),
+    ("mt", "America/Denver"),
+    ("cst", "America/Chicago"),
+    ("cdt", "America/Chicago"),
+    ("ct", "America/Chicago"),
+    ("est", "America/New_York"),
+    ("edt", "America/New_York"),
+    ("et", "America/New_York"),
+    ("ast", "America/Halifax"),
+    ("adt", "America/Halifax"),
+    ("nst", "America/St_Johns"),
+    ("ndt", "America/St_Johns"),
+    ("akst", "America/Anchorage"),
+    ("akdt", "America/Anchorage"),
+    ("hst", "Pacific/Honolulu"),
+    ("gmt", "Europe/London"),
+    ("bst", "Europe/London"),
+    ("wet", "Europe/Lisbon"),
+    ("west", "Europe/Lisbon"),
+    ("cet", "Europe/Paris"),
+    ("cest", "Europe/Paris"),
+    ("eet", "Europe/Athens"),
+    ("eest", "Europe/Athens"),
+    ("msk", "Europe/Moscow"),
+    ("ist", "Asia/Kolkata"),
+    ("jst", "Asia/Tokyo"),
+    ("kst", "Asia/Seoul"),
+    ("awst", "Australia/Perth"),
+    ("acst", "Australia/Adelaide"),
+    ("acdt", "Australia/Adelaide"),
+    ("aest", "Australia/Sydney"),
+    ("aedt", "Australia/Sydney"),
+    ("nzst", "Pacific/Auckland"),
+    ("nzdt", "Pacific/Auckland"),
+];
+
+/// Finds the timezone with the given name or abbreviation, case-insensitive.
+/// eg. "america/vancouver" or "pst".
+pub fn find_timezone(name: &str) -> Option<Tz> {
+    let name = ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| abbreviation.eq_ignore_ascii_case(name))
+        .map_or(name, |(_, region)| region);
+
+    TZ_VARIANTS
+        .iter()
+        .find(|tz| tz.name().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// Returns the offset from UTC in minutes of the timezone at that moment.
+pub fn offset_at(tz: Tz, at: DateTime<Utc>) -> i32 {
+    tz.offset_from_utc_datetime(&at.naive_utc())
+        .fix()
+        .local_minus_utc()
+        / 60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_timezone() {
+        assert_eq!(
+            find_timezone("america/vancouver"),
+            Some(chrono_tz::America::Vancouver)
+        );
+        assert_eq!(find_timezone("PST"), Some(chrono_tz::America::Los_Angeles));
+        assert_eq!(find_timezone("cet"), Some(chrono_tz::Europe::Paris));
+        assert_eq!(find_timezone("vancouver"), None);
+
+        let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+        let vancouver = chrono_tz::America::Vancouver;
+        assert_eq!(offset_at(vancouver, winter), -480);
+        assert_eq!(offset_at(vancouver, summer), -420);
+        assert_eq!(offset_at(chrono_tz::Asia::Kathmandu, summer), 345);
+    }
+}
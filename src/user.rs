@@ -7,25 +7,113 @@
 // Licensed under Apache License v2.0
 //===----------------------------------------------------------------------===//
 use crate::day::*;
+use crate::timezone::{find_timezone, offset_at};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// The length of a single slot in the schedule, in minutes.
+/// Must evenly divide an hour, and a day may have at most 128 slots.
+pub const SLOT_MINUTES: u32 = 15;
+const SLOTS_PER_HOUR: u32 = 60 / SLOT_MINUTES;
+const SLOTS_PER_DAY: u32 = 24 * SLOTS_PER_HOUR;
 
 /// Represents a single user's schedule.
-/// The schedule itself is stored in UTC time as an int (used as a bit vector).
-/// eg. 0b010000000000000000000001 represents availability at 0 and 22.
-#[derive(Serialize, Deserialize, Debug)]
+/// The schedule itself is stored in UTC time as an int (used as a bit vector),
+/// where every bit is a slot of SLOT_MINUTES minutes.
+/// eg. 0b1111 represents availability from 0:00 to 1:00.
+/// The timezone is stored as an offset from UTC in minutes.
+/// If the user gave the name of their timezone, the schedule is stored
+/// relative to the offset at the time it was set, and is moved whenever
+/// daylight saving time changes the offset.
+/// Specific dates may override the weekly schedule, see DateOverride.
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     name: String,
+    /// The name of the user's account, which others may refer to them by.
+    #[serde(default)]
+    account_name: String,
+    /// The user's nickname in the guild, if they have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nickname: Option<String>,
+    #[serde(rename = "utc_offset")]
     timezone: i32,
-    schedule: [u32; 7],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timezone_name: Option<String>,
+    #[serde(rename = "slots")]
+    schedule: [u128; 7],
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    overrides: BTreeMap<NaiveDate, DateOverride>,
+}
+
+/// Exceptions to the weekly schedule on a specific date.
+/// Slots are stored in the user's local time, as bit vectors like the schedule.
+/// Slots which are away are unavailable, and extra slots are available,
+/// regardless of the weekly schedule.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct DateOverride {
+    away: u128,
+    extra: u128,
+}
+
+/// The user as it is read from the data file, in any version of the format.
+/// Fields which are no longer used are kept until they are migrated, see schema.rs.
+#[derive(Deserialize)]
+pub struct StoredUser {
+    /// The display name.
+    pub name: String,
+    /// The name of the account, since version 4.
+    #[serde(default)]
+    pub account_name: String,
+    /// The nickname in the guild, since version 4.
+    #[serde(default)]
+    pub nickname: Option<String>,
+    /// The timezone in minutes, since version 2.
+    #[serde(default)]
+    pub utc_offset: Option<i32>,
+    /// The timezone in hours, before version 2.
+    #[serde(default)]
+    pub timezone: Option<i32>,
+    /// The name of the timezone, if the user gave one.
+    #[serde(default)]
+    pub timezone_name: Option<String>,
+    /// The schedule with one bit per 15 minutes, since version 3.
+    #[serde(default)]
+    pub slots: Option<[u128; 7]>,
+    /// The schedule with one bit per hour, before version 3.
+    #[serde(default)]
+    pub schedule: Option<[u32; 7]>,
+    /// Changes to the schedule on specific dates.
+    #[serde(default)]
+    pub overrides: BTreeMap<NaiveDate, DateOverride>,
+}
+
+/// Assumes the user was migrated to the current version of the format.
+impl From<StoredUser> for User {
+    fn from(user: StoredUser) -> User {
+        User {
+            name: user.name,
+            account_name: user.account_name,
+            nickname: user.nickname,
+            timezone: user.utc_offset.unwrap_or(0),
+            timezone_name: user.timezone_name,
+            schedule: user.slots.unwrap_or([0; 7]),
+            overrides: user.overrides,
+        }
+    }
 }
 
 impl User {
     pub fn new(name: String) -> User {
         Self {
+            account_name: name.clone(),
+            nickname: None,
             name,
             schedule: [0; 7],
             timezone: 0,
+            timezone_name: None,
+            overrides: BTreeMap::new(),
         }
     }
 
@@ -39,98 +127,175 @@ impl User {
         self.name = name;
     }
 
-    /// Retrieves the user's timezone.
+    /// Retrieves the name of the user's account.
+    pub fn account_name(&self) -> String {
+        self.account_name.clone()
+    }
+
+    /// Sets the name of the user's account.
+    pub fn set_account_name(&mut self, account_name: String) {
+        self.account_name = account_name;
+    }
+
+    /// Retrieves the user's nickname in the guild.
+    pub fn nickname(&self) -> Option<String> {
+        self.nickname.clone()
+    }
+
+    /// Sets the user's nickname in the guild.
+    pub fn set_nickname(&mut self, nickname: Option<String>) {
+        self.nickname = nickname;
+    }
+
+    /// Retrieves the user's timezone, in minutes from UTC.
     pub fn timezone(&self) -> i32 {
         self.timezone
     }
 
-    /// Sets the user's timezone.
+    /// Sets the user's timezone, in minutes from UTC.
     pub fn set_timezone(&mut self, timezone: i32) {
         // Schedule is shifted in 2 steps to maintain <24 hr changes
         // first shifts schedule to UTC
         // second shifts to new timezone
-        self.schedule = shift_schedule(self.schedule, self.timezone);
-        self.schedule = shift_schedule(self.schedule, -timezone);
+        self.schedule = shift_schedule(self.schedule, slot_shift(self.timezone));
+        self.schedule = shift_schedule(self.schedule, -slot_shift(timezone));
         self.timezone = timezone;
+        self.timezone_name = None;
     }
 
-    /// Checks if the user is available at that time and day in that timezone.
-    pub fn is_available(&self, day: Day, time: u32, timezone: i32) -> bool {
-        let (day, time) = global_daytime(day, time, timezone);
-        self.schedule[day as usize] & (1 << time) > 0
-    }
-
-    /// Returns a simple string representation of the user's schedule.
-    pub fn disp_schedule(&self, time_as_row: bool, timezone: i32) -> String {
-        let shift_schedule = shift_schedule(self.schedule, timezone);
-
-        // If time_as_row is true, the days will be the columns,
-        // otherwise they are the rows.
-        match time_as_row {
-            true => (0..24)
-                .map(|bit| {
-                    format!("{:0>2}", bit.to_string())
-                        + ": "
-                        + &(shift_schedule
-                            .iter()
-                            .map(move |times| match times & (1 << bit) {
-                                0 => "░ ",
-                                _ => "█ ",
-                            })
-                            .collect::<String>())
-                        + "\n"
-                })
-                .collect::<String>(),
-            _ => {
-                "     012345678901234567890123\n".to_string()
-                    + &(shift_schedule
-                        .iter()
-                        .enumerate()
-                        .map(|times| {
-                            num_to_day(times.0 as u32).unwrap().to_string()
-                                + ": "
-                                + &((0..24)
-                                    .map(|bit| match times.1 & (1 << bit) {
-                                        0 => '░',
-                                        _ => '█',
-                                    })
-                                    .collect::<String>())
-                                + "\n"
-                        })
-                        .collect::<String>())
-            }
+    /// Retrieves the name of the user's timezone, if they set one.
+    pub fn timezone_name(&self) -> Option<String> {
+        self.timezone_name.clone()
+    }
+
+    /// Sets the user's timezone by name, eg. "America/Vancouver".
+    /// The schedule is kept relative to the offset the timezone has at that moment.
+    pub fn set_timezone_name(&mut self, name: &str, at: DateTime<Utc>) -> Result<(), &'static str> {
+        let tz = find_timezone(name).ok_or("Unknown timezone")?;
+        self.set_timezone(offset_at(tz, at));
+        self.timezone_name = Some(tz.name().to_string());
+        Ok(())
+    }
+
+    /// Retrieves the user's offset from UTC in minutes at that moment,
+    /// accounting for daylight saving time if the user set a timezone name.
+    pub fn offset_at(&self, at: DateTime<Utc>) -> i32 {
+        match self
+            .timezone_name
+            .as_ref()
+            .and_then(|name| find_timezone(name))
+        {
+            Some(tz) => offset_at(tz, at),
+            None => self.timezone,
         }
     }
 
-    /// Sets the time on the specified day to available or unavailable.
+    /// Returns the user's timezone as it should be displayed at that moment,
+    /// eg. "America/Vancouver (-7:00)" or "+5:30".
+    pub fn disp_timezone(&self, at: DateTime<Utc>) -> String {
+        match &self.timezone_name {
+            Some(name) => format!("{} ({})", name, timezone_to_string(self.offset_at(at))),
+            None => timezone_to_string(self.timezone),
+        }
+    }
+
+    /// Converts a time (in minutes) on a date in the user's timezone to UTC.
+    pub fn date_to_utc(&self, date: NaiveDate, time: u32) -> DateTime<Utc> {
+        let local = date.and_hms_opt(0, 0, 0).unwrap() + Duration::minutes(time as i64);
+        // The offset is found at roughly the right moment first,
+        // since it may be different at that moment than it is now.
+        let guess = DateTime::from_naive_utc_and_offset(
+            local - Duration::minutes(self.timezone as i64),
+            Utc,
+        );
+        DateTime::from_naive_utc_and_offset(
+            local - Duration::minutes(self.offset_at(guess) as i64),
+            Utc,
+        )
+    }
+
+    /// Converts a moment to the date and time it is in the user's timezone.
+    pub fn utc_to_local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.naive_utc() + Duration::minutes(self.offset_at(at) as i64)
+    }
+
+    /// Finds the next time the day and time (in minutes) occur in the user's timezone,
+    /// starting from that moment.
+    pub fn next_occurrence(&self, day: Day, time: u32, at: DateTime<Utc>) -> DateTime<Utc> {
+        let today = self.utc_to_local(at).date();
+        let days_ahead = (day as i64 - weekday_to_day(today.weekday()) as i64).rem_euclid(7);
+        let next = self.date_to_utc(today + Duration::days(days_ahead), time);
+        match next < at {
+            true => self.date_to_utc(today + Duration::days(days_ahead + 7), time),
+            false => next,
+        }
+    }
+
+    /// Returns the difference in minutes between the user's offset at that moment,
+    /// and the offset their schedule was stored with.
+    fn offset_drift(&self, at: DateTime<Utc>) -> i32 {
+        self.offset_at(at) - self.timezone
+    }
+
+    /// Checks if the user is available at that time (in minutes) and day
+    /// in that timezone, during the week of the given moment.
+    pub fn is_available(&self, day: Day, time: u32, timezone: i32, at: DateTime<Utc>) -> bool {
+        let (day, slot) = global_daytime(day, time, timezone - self.offset_drift(at));
+        self.schedule[day as usize] & (1 << slot) > 0
+    }
+
+    /// Returns the user's schedule shifted to the timezone (in minutes),
+    /// during the week of the given moment.
+    pub fn local_schedule(&self, timezone: i32, at: DateTime<Utc>) -> [u128; 7] {
+        shift_schedule(self.schedule, slot_shift(timezone - self.offset_drift(at)))
+    }
+
+    /// Returns a simple string representation of the user's schedule,
+    /// during the week of the given moment.
+    pub fn disp_schedule(&self, time_as_row: bool, timezone: i32, at: DateTime<Utc>) -> String {
+        schedule_to_string(
+            self.local_schedule(timezone, at),
+            time_as_row,
+            &day_range(Day::Sun, Day::Sat),
+        )
+    }
+
+    /// Sets the hour starting at the time (in minutes) on the specified day
+    /// to available or unavailable.
     pub fn set_time(&mut self, day: Day, time: u32, available: bool) {
-        let (day, time) = global_daytime(day, time, self.timezone);
-        match available {
-            true => self.schedule[day as usize] |= 1 << time,
-            false => self.schedule[day as usize] &= u32::max_value() - (1 << time),
-        };
+        self.set_time_range(day, time, time + 60, available);
     }
 
-    /// Sets the range of times (inclusive) on the specified day to available or
-    /// unavailable.
+    /// Sets the range of times (in minutes) on the specified day to available or
+    /// unavailable. The start time is inclusive, and the end time is exclusive.
+    /// Ranges which end before they start continue past midnight into the next day.
     pub fn set_time_range(&mut self, day: Day, start_time: u32, end_time: u32, available: bool) {
-        for time in start_time..=end_time {
-            self.set_time(day, time, available);
+        for time in (start_time..wrap_end(start_time, end_time)).step_by(SLOT_MINUTES as usize) {
+            self.set_slot(day, time, available);
         }
     }
 
-    /// Sets the specified time in the range of days to available or unavailable.
+    /// Sets the slot containing the time (in minutes) on the specified day
+    /// to available or unavailable.
+    fn set_slot(&mut self, day: Day, time: u32, available: bool) {
+        let (day, slot) = global_daytime(day, time, self.timezone);
+        match available {
+            true => self.schedule[day as usize] |= 1 << slot,
+            false => self.schedule[day as usize] &= u128::MAX - (1 << slot),
+        };
+    }
+
+    /// Sets the hour starting at the specified time in the range of days
+    /// to available or unavailable.
     /// Ranges are inclusive.
     pub fn set_day_range(&mut self, start_day: Day, end_day: Day, time: u32, available: bool) {
-        let end_num;
-
         // If the range is from a later day to an earlier day,
         // we still want to iterate. Eg., from Fri to Tue.
-        if end_day < start_day {
-            end_num = end_day as u32 + 7;
+        let end_num = if end_day < start_day {
+            end_day as u32 + 7
         } else {
-            end_num = end_day as u32;
-        }
+            end_day as u32
+        };
 
         for day_num in (start_day as u32)..=(end_num) {
             self.set_time(num_to_day(day_num % 7).unwrap(), time, available);
@@ -138,7 +303,7 @@ impl User {
     }
 
     /// Sets the range of times and range of days to available or unavailable.
-    /// Ranges are inclusive.
+    /// The range of days is inclusive, the range of times is as in set_time_range.
     pub fn set_day_time_range(
         &mut self,
         start_day: Day,
@@ -147,15 +312,13 @@ impl User {
         end_time: u32,
         available: bool,
     ) {
-        let end_num;
-
         // If the range is from a later day to an earlier day,
         // we still want to iterate. Eg., from Fri to Tue.
-        if end_day < start_day {
-            end_num = end_day as u32 + 7;
+        let end_num = if end_day < start_day {
+            end_day as u32 + 7
         } else {
-            end_num = end_day as u32;
-        }
+            end_day as u32
+        };
 
         for day_num in (start_day as u32)..=(end_num) {
             self.set_time_range(
@@ -167,55 +330,235 @@ impl User {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn get_raw_schedule(&self) -> [u32; 7] {
+    /// Sets the range of times (in minutes) on the date to away (unavailable)
+    /// or extra (available), in place of the weekly schedule.
+    /// Times are in the user's own timezone, the end time is exclusive,
+    /// and times past the end of the day, or ranges which end before they start,
+    /// continue on the next date.
+    /// Overrides for dates which have already passed at that moment are removed.
+    pub fn set_date_range(
+        &mut self,
+        date: NaiveDate,
+        start_time: u32,
+        end_time: u32,
+        available: bool,
+        at: DateTime<Utc>,
+    ) {
+        for time in (start_time..wrap_end(start_time, end_time)).step_by(SLOT_MINUTES as usize) {
+            let date = date + Duration::days((time / (24 * 60)) as i64);
+            let slot: u128 = 1 << ((time % (24 * 60)) / SLOT_MINUTES);
+            let date_override = self.overrides.entry(date).or_default();
+            match available {
+                true => {
+                    date_override.extra |= slot;
+                    date_override.away &= !slot;
+                }
+                false => {
+                    date_override.away |= slot;
+                    date_override.extra &= !slot;
+                }
+            }
+        }
+
+        let today = self.utc_to_local(at).date();
+        self.overrides.retain(|date, _| *date >= today);
+    }
+
+    /// Checks if the user is available at that time (in minutes) on that date
+    /// in that timezone, accounting for the overrides of the date.
+    pub fn is_available_on(&self, date: NaiveDate, time: u32, timezone: i32) -> bool {
+        let utc =
+            date.and_hms_opt(0, 0, 0).unwrap() + Duration::minutes(time as i64 - timezone as i64);
+        let at = DateTime::from_naive_utc_and_offset(utc, Utc);
+        let local = utc + Duration::minutes(self.offset_at(at) as i64);
+        let slot: u128 = 1 << (local.num_seconds_from_midnight() / 60 / SLOT_MINUTES);
+
+        match self.overrides.get(&local.date()) {
+            Some(date_override) if date_override.away & slot > 0 => false,
+            Some(date_override) if date_override.extra & slot > 0 => true,
+            _ => self.is_available(weekday_to_day(date.weekday()), time, timezone, at),
+        }
+    }
+
+    /// Returns a list of the dates which override the weekly schedule,
+    /// with the ranges of times the user is away or has extra time.
+    pub fn disp_overrides(&self) -> String {
+        self.overrides
+            .iter()
+            .map(|(date, date_override)| {
+                let mut changes = vec![];
+                if date_override.away > 0 {
+                    changes.push("away ".to_string() + &slots_to_string(date_override.away));
+                }
+                if date_override.extra > 0 {
+                    changes.push("extra ".to_string() + &slots_to_string(date_override.extra));
+                }
+                date.to_string() + ": " + &changes.join(", ") + "\n"
+            })
+            .collect::<String>()
+    }
+
+    /// Retrieves the slots of every day, in UTC.
+    pub fn get_raw_schedule(&self) -> [u128; 7] {
         self.schedule
     }
 
-    #[allow(dead_code)]
-    pub fn set_raw_schedule(&mut self, schedule: [u32; 7]) {
+    /// Replaces the slots of every day, in UTC.
+    pub fn set_raw_schedule(&mut self, schedule: [u128; 7]) {
         self.schedule = schedule;
     }
 }
 
-/// Converts the local day and time to UTC.
+/// Converts the local day and time (in minutes) to the UTC day and slot,
+/// given the timezone in minutes.
+/// Times which are not at the start of a slot are placed in the slot containing them.
 fn global_daytime(day: Day, time: u32, timezone: i32) -> (Day, u32) {
-    let new_time = time as i32 - timezone;
-    let day_shift: u32;
-    if new_time < 0 {
-        day_shift = 6;
-    } else if new_time >= 24 {
-        day_shift = 1;
+    let new_slot = (time as i32 - timezone).div_euclid(SLOT_MINUTES as i32);
+    let day_shift = new_slot.div_euclid(SLOTS_PER_DAY as i32);
+
+    (
+        num_to_day((day as i32 + day_shift).rem_euclid(7) as u32).unwrap(),
+        new_slot.rem_euclid(SLOTS_PER_DAY as i32) as u32,
+    )
+}
+
+/// Returns the end of a range of times (in minutes), moved to the next day
+/// if the range ends before it starts, eg. from 22:00 to 3:00.
+/// A range which ends when it starts covers the whole day, eg. from 18 to 17.
+pub fn wrap_end(start_time: u32, end_time: u32) -> u32 {
+    if end_time <= start_time {
+        end_time + 24 * 60
     } else {
-        day_shift = 0;
+        end_time
     }
+}
 
-    (
-        num_to_day(((day as u32) + day_shift) % 7).unwrap(),
-        (new_time + 24) as u32 % 24,
+/// Returns the number of slots the UTC schedule must be shifted by
+/// to line up with the local slots of the timezone (in minutes).
+/// This matches the rounding done by global_daytime.
+fn slot_shift(timezone: i32) -> i32 {
+    -(-timezone).div_euclid(SLOT_MINUTES as i32)
+}
+
+/// Returns a simple string representation of a schedule which is in local time,
+/// showing only the given days.
+pub fn schedule_to_string(schedule: [u128; 7], time_as_row: bool, days: &[Day]) -> String {
+    // If time_as_row is true, the days will be the columns,
+    // with one character per slot of the hour.
+    // Otherwise they are the rows, with one character per hour.
+    match time_as_row {
+        true => (0..24)
+            .map(|hour| {
+                format!("{:0>2}", hour.to_string())
+                    + ": "
+                    + &(days
+                        .iter()
+                        .map(move |day| {
+                            let slots = schedule[*day as usize];
+                            (0..SLOTS_PER_HOUR)
+                                .map(|slot| match slots & (1 << (hour * SLOTS_PER_HOUR + slot)) {
+                                    0 => '░',
+                                    _ => '█',
+                                })
+                                .collect::<String>()
+                                + " "
+                        })
+                        .collect::<String>())
+                    + "\n"
+            })
+            .collect::<String>(),
+        _ => {
+            "     012345678901234567890123\n".to_string()
+                + &(days
+                    .iter()
+                    .map(|day| {
+                        let slots = schedule[*day as usize];
+                        day.to_string()
+                            + ": "
+                            + &((0..24)
+                                .map(|hour| {
+                                    let hour_mask = hours_to_slots(1 << hour);
+                                    match slots & hour_mask {
+                                        0 => '░',
+                                        bits if bits == hour_mask => '█',
+                                        _ => '▒',
+                                    }
+                                })
+                                .collect::<String>())
+                            + "\n"
+                    })
+                    .collect::<String>())
+        }
+    }
+}
+
+/// Converts a day stored with one bit per hour into one bit per slot.
+pub fn hours_to_slots(hours: u32) -> u128 {
+    (0..24)
+        .filter(|hour| hours & (1 << hour) > 0)
+        .map(|hour| ((1 << SLOTS_PER_HOUR) - 1) << (hour * SLOTS_PER_HOUR))
+        .sum()
+}
+
+/// Formats a timezone in minutes as an offset from UTC, eg. "+5:30" or "-7:00".
+pub fn timezone_to_string(timezone: i32) -> String {
+    format!(
+        "{}{}:{:0>2}",
+        if timezone < 0 { '-' } else { '+' },
+        timezone.abs() / 60,
+        timezone.abs() % 60
     )
 }
 
-/// Converts the schedule (stored as UTC time) to match the specified timezone.
-fn shift_schedule(schedule: [u32; 7], timezone: i32) -> [u32; 7] {
+/// Formats the slots of a day as a list of ranges of times, eg. "18:00-20:30 22:00-24:00".
+fn slots_to_string(slots: u128) -> String {
+    let mut ranges = vec![];
+    let mut start = None;
+    for slot in 0..=SLOTS_PER_DAY {
+        let is_set = slot < SLOTS_PER_DAY && slots & (1 << slot) > 0;
+        match (start, is_set) {
+            (None, true) => start = Some(slot),
+            (Some(start_slot), false) => {
+                ranges.push(
+                    time_to_string(start_slot * SLOT_MINUTES)
+                        + "-"
+                        + &time_to_string(slot * SLOT_MINUTES),
+                );
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    ranges.join(" ")
+}
+
+/// Formats a time of day in minutes, eg. "18:30".
+pub fn time_to_string(time: u32) -> String {
+    format!("{:0>2}:{:0>2}", time / 60, time % 60)
+}
+
+/// Converts the schedule (stored as UTC time) to match the specified timezone,
+/// given as a number of slots.
+pub fn shift_schedule(schedule: [u128; 7], shift: i32) -> [u128; 7] {
     let mut res = schedule;
+    let day_mask = (1 << SLOTS_PER_DAY) - 1;
 
     // Depending on if the timezone goes forward or backwards in time,
     // the operations may be reversed.
     // Bit shifts are done to move the bits which are now in another day.
-    match timezone.cmp(&0) {
+    match shift.cmp(&0) {
         Ordering::Greater => {
             for day in 0..7 {
-                res[(day + 1) % 7] = ((schedule[(day + 1) % 7] << timezone) % (1 << 24))
-                    | schedule[day] >> (24 - timezone) as u32;
+                res[(day + 1) % 7] = ((schedule[(day + 1) % 7] << shift) & day_mask)
+                    | schedule[day] >> (SLOTS_PER_DAY as i32 - shift) as u32;
             }
         }
         Ordering::Equal => (),
         Ordering::Less => {
             for day in 0..7 {
-                let shift = -timezone as u32;
+                let shift = -shift as u32;
                 res[(day + 6) % 7] = schedule[(day + 6) % 7] >> shift
-                    | ((schedule[day] << (24 - shift)) % (1 << 24));
+                    | ((schedule[day] << (SLOTS_PER_DAY - shift)) & day_mask);
             }
         }
     }
@@ -225,33 +568,35 @@ fn shift_schedule(schedule: [u32; 7], timezone: i32) -> [u32; 7] {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schema;
+    use chrono::TimeZone;
 
     #[test]
     fn test_local_timezone() {
         let ans = shift_schedule(
             [
-                (1 << 3) + (1 << 4),
-                (1 << 3) + (1 << 4) + (1 << 5) + (1 << 6),
+                hours_to_slots((1 << 3) + (1 << 4)),
+                hours_to_slots((1 << 3) + (1 << 4) + (1 << 5) + (1 << 6)),
                 0,
                 0,
                 0,
                 0,
-                (1 << 3) + (1 << 4),
+                hours_to_slots((1 << 3) + (1 << 4)),
             ],
-            -5,
+            -5 * SLOTS_PER_HOUR as i32,
         );
         for day in ans.iter() {
-            println!("{:024b}", day);
+            println!("{:096b}", day);
         }
         assert_eq!(
             [
-                (1 << 22) + (1 << 23),
-                3,
+                hours_to_slots((1 << 22) + (1 << 23)),
+                hours_to_slots(3),
                 0,
                 0,
                 0,
-                (1 << 22) + (1 << 23),
-                (1 << 22) + (1 << 23)
+                hours_to_slots((1 << 22) + (1 << 23)),
+                hours_to_slots((1 << 22) + (1 << 23))
             ],
             ans
         );
@@ -260,13 +605,234 @@ mod tests {
     #[test]
     fn test_set_timezone() {
         let mut usr = User::new("bob".to_string());
-        usr.set_timezone(2);
-        usr.set_raw_schedule([1, 1 << 23, 0, 1, 0, 1 << 23, 1 << 5]);
-        println!("{}", usr.disp_schedule(true, 2));
-        usr.set_timezone(-1);
-        println!("{}", usr.disp_schedule(true, -1));
-        assert_eq!(usr.get_raw_schedule()[0], 8);
+        usr.set_timezone(120);
+        usr.set_raw_schedule([
+            hours_to_slots(1),
+            hours_to_slots(1 << 23),
+            0,
+            hours_to_slots(1),
+            0,
+            hours_to_slots(1 << 23),
+            hours_to_slots(1 << 5),
+        ]);
+        println!("{}", usr.disp_schedule(true, 120, Utc::now()));
+        usr.set_timezone(-60);
+        println!("{}", usr.disp_schedule(true, -60, Utc::now()));
+        assert_eq!(usr.get_raw_schedule()[0], hours_to_slots(8));
         assert_eq!(usr.get_raw_schedule()[1], 0);
-        assert_eq!(usr.get_raw_schedule()[6], (1 << 8) + 4);
+        assert_eq!(usr.get_raw_schedule()[6], hours_to_slots((1 << 8) + 4));
+    }
+
+    #[test]
+    fn test_partial_hour_timezone() {
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone(330);
+        usr.set_time_range(Day::Mon, 18 * 60, 20 * 60, true);
+        assert_eq!(
+            usr.get_raw_schedule()[1],
+            hours_to_slots(1 << 13) | (0b11 << (12 * 4 + 2)) | (0b11 << (14 * 4))
+        );
+        assert!(usr.is_available(Day::Mon, 18 * 60, 330, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 17 * 60 + 45, 330, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 12 * 60, 0, Utc::now()));
+        assert!(usr.is_available(Day::Mon, 12 * 60 + 30, 0, Utc::now()));
+
+        usr.set_timezone(-210);
+        assert!(usr.is_available(Day::Mon, 18 * 60, -210, Utc::now()));
+        assert!(usr.is_available(Day::Mon, 19 * 60 + 45, -210, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 20 * 60, -210, Utc::now()));
+
+        usr.set_timezone(345);
+        assert_eq!(
+            usr.disp_schedule(false, 345, Utc::now())
+                .lines()
+                .nth(2)
+                .unwrap(),
+            "Mon: ░░░░░░░░░░░░░░░░░░██░░░░"
+        );
+        assert_eq!(
+            usr.disp_schedule(false, 0, Utc::now())
+                .lines()
+                .nth(2)
+                .unwrap(),
+            "Mon: ░░░░░░░░░░░░▒█▒░░░░░░░░░"
+        );
+        assert_eq!(timezone_to_string(345), "+5:45");
+        assert_eq!(timezone_to_string(-210), "-3:30");
+        assert_eq!(timezone_to_string(-420), "-7:00");
+    }
+
+    #[test]
+    fn test_slots() {
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone(-420);
+        usr.set_time_range(Day::Fri, 18 * 60 + 30, 20 * 60, true);
+        assert!(!usr.is_available(Day::Fri, 18 * 60 + 15, -420, Utc::now()));
+        assert!(usr.is_available(Day::Fri, 18 * 60 + 30, -420, Utc::now()));
+        assert!(usr.is_available(Day::Fri, 19 * 60 + 59, -420, Utc::now()));
+        assert!(!usr.is_available(Day::Fri, 20 * 60, -420, Utc::now()));
+        assert_eq!(
+            usr.disp_schedule(true, -420, Utc::now())
+                .lines()
+                .nth(18)
+                .unwrap(),
+            "18: ░░░░ ░░░░ ░░░░ ░░░░ ░░░░ ░░██ ░░░░ "
+        );
+
+        usr.set_time(Day::Sat, 23 * 60 + 30, true);
+        assert!(usr.is_available(Day::Sat, 23 * 60 + 45, -420, Utc::now()));
+        assert!(usr.is_available(Day::Sun, 15, -420, Utc::now()));
+        assert!(!usr.is_available(Day::Sun, 30, -420, Utc::now()));
+        assert_eq!(time_to_string(18 * 60 + 30), "18:30");
+    }
+
+    #[test]
+    fn test_daylight_saving() {
+        let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone_name("America/Vancouver", winter).unwrap();
+        assert_eq!(usr.timezone(), -480);
+        usr.set_time_range(Day::Fri, 18 * 60, 22 * 60, true);
+
+        assert!(usr.is_available(Day::Fri, 18 * 60, -480, winter));
+        assert!(usr.is_available(Day::Fri, 18 * 60, -420, summer));
+        assert!(!usr.is_available(Day::Fri, 22 * 60, -420, summer));
+        assert!(usr.is_available(Day::Sat, 2 * 60, 0, winter));
+        assert!(!usr.is_available(Day::Sat, 6 * 60, 0, winter));
+        assert!(usr.is_available(Day::Sat, 60, 0, summer));
+        assert!(!usr.is_available(Day::Sat, 5 * 60, 0, summer));
+        assert_eq!(
+            usr.disp_schedule(true, usr.offset_at(summer), summer),
+            usr.disp_schedule(true, usr.offset_at(winter), winter)
+        );
+        assert_eq!(usr.disp_timezone(summer), "America/Vancouver (-7:00)");
+
+        usr.set_timezone(-420);
+        assert_eq!(usr.timezone_name(), None);
+        assert!(usr.is_available(Day::Fri, 18 * 60, -420, winter));
+        assert!(usr.is_available(Day::Fri, 18 * 60, -420, summer));
+    }
+
+    /// Reads a user saved by an older version of the bot.
+    fn read_user(serialized: &str) -> User {
+        let guilds = schema::parse(&format!("{{\"users\": {{\"1\": {}}}}}", serialized)).unwrap();
+        guilds.legacy().unwrap().user(1).unwrap().clone()
+    }
+
+    #[test]
+    fn test_read_hour_timezone() {
+        let usr = read_user(r#"{"name":"bob","timezone":-7,"schedule":[0,0,0,0,0,0,0]}"#);
+        assert_eq!(usr.timezone(), -420);
+    }
+
+    #[test]
+    fn test_read_hour_schedule() {
+        let usr = read_user(r#"{"name":"bob","utc_offset":330,"schedule":[0,4096,0,0,0,0,0]}"#);
+        assert!(usr.is_available(Day::Mon, 18 * 60, 330, Utc::now()));
+        assert!(usr.is_available(Day::Mon, 18 * 60 + 45, 330, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 19 * 60, 330, Utc::now()));
+
+        let json = serde_json::to_string(&usr).unwrap();
+        let usr: User = serde_json::from_str(&json).unwrap();
+        assert!(usr.is_available(Day::Mon, 18 * 60, 330, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 19 * 60, 330, Utc::now()));
+    }
+
+    #[test]
+    fn test_midnight_ranges() {
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone(-420);
+        usr.set_time_range(Day::Fri, 22 * 60, 3 * 60, true);
+        assert!(!usr.is_available(Day::Fri, 21 * 60 + 45, -420, Utc::now()));
+        assert!(usr.is_available(Day::Fri, 22 * 60, -420, Utc::now()));
+        assert!(usr.is_available(Day::Sat, 0, -420, Utc::now()));
+        assert!(usr.is_available(Day::Sat, 2 * 60 + 45, -420, Utc::now()));
+        assert!(!usr.is_available(Day::Sat, 3 * 60, -420, Utc::now()));
+
+        // Ranges which end when they start wrap round the whole day.
+        let mut full = User::new("carol".to_string());
+        full.set_time_range(Day::Fri, 18 * 60, 18 * 60, true);
+        assert!(!full.is_available(Day::Fri, 17 * 60 + 45, 0, Utc::now()));
+        assert!(full.is_available(Day::Fri, 18 * 60, 0, Utc::now()));
+        assert!(full.is_available(Day::Sat, 17 * 60 + 45, 0, Utc::now()));
+        assert!(!full.is_available(Day::Sat, 18 * 60, 0, Utc::now()));
+
+        // Ranges ending on Sun wrap into Mon, as day ranges wrap into the next week.
+        usr.set_day_time_range(Day::Sat, Day::Sun, 23 * 60, 60, true);
+        assert!(usr.is_available(Day::Sun, 30, -420, Utc::now()));
+        assert!(usr.is_available(Day::Mon, 30, -420, Utc::now()));
+        assert!(!usr.is_available(Day::Mon, 60, -420, Utc::now()));
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 30, 12, 0, 0).unwrap();
+        let friday = NaiveDate::from_ymd_opt(2026, 11, 6).unwrap();
+        usr.set_date_range(friday, 22 * 60, 60, false, now);
+        assert_eq!(
+            usr.disp_overrides(),
+            "2026-11-06: away 22:00-24:00\n\
+             2026-11-07: away 00:00-01:00\n"
+        );
+    }
+
+    #[test]
+    fn test_date_overrides() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 30, 12, 0, 0).unwrap();
+        let tuesday = NaiveDate::from_ymd_opt(2026, 11, 3).unwrap();
+        let thursday = NaiveDate::from_ymd_opt(2026, 11, 5).unwrap();
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone(-480);
+        usr.set_day_time_range(Day::Mon, Day::Fri, 18 * 60, 24 * 60, true);
+
+        usr.set_date_range(tuesday, 18 * 60, 24 * 60, false, now);
+        usr.set_date_range(thursday, 20 * 60, 22 * 60, true, now);
+        usr.set_date_range(thursday, 23 * 60, 25 * 60, true, now);
+
+        assert!(!usr.is_available_on(tuesday, 19 * 60, -480));
+        assert!(!usr.is_available_on(tuesday + Duration::days(1), 3 * 60, 0));
+        assert!(usr.is_available_on(tuesday, 3 * 60, 0));
+        assert!(usr.is_available_on(tuesday - Duration::days(7), 19 * 60, -480));
+        assert!(usr.is_available_on(tuesday + Duration::days(1), 19 * 60, -480));
+        assert!(usr.is_available_on(thursday, 21 * 60, -480));
+        assert!(usr.is_available_on(thursday + Duration::days(1), 30, -480));
+        assert!(!usr.is_available_on(thursday + Duration::days(1), 60, -480));
+        assert_eq!(
+            usr.disp_overrides(),
+            "2026-11-03: away 18:00-24:00\n\
+             2026-11-05: extra 20:00-22:00 23:00-24:00\n\
+             2026-11-06: extra 00:00-01:00\n"
+        );
+
+        usr.set_date_range(tuesday, 20 * 60, 21 * 60, true, now);
+        assert!(usr.is_available_on(tuesday, 20 * 60, -480));
+        assert!(!usr.is_available_on(tuesday, 21 * 60, -480));
+
+        let later = Utc.with_ymd_and_hms(2026, 11, 5, 12, 0, 0).unwrap();
+        usr.set_date_range(thursday, 0, 60, false, later);
+        assert!(usr.is_available_on(tuesday, 19 * 60, -480));
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        // Monday 2026-11-02 at 03:00 UTC is still Sunday in Vancouver.
+        let now = Utc.with_ymd_and_hms(2026, 11, 2, 3, 0, 0).unwrap();
+        let mut usr = User::new("bob".to_string());
+        usr.set_timezone_name("America/Vancouver", now).unwrap();
+        assert_eq!(
+            usr.next_occurrence(Day::Fri, 20 * 60, now),
+            Utc.with_ymd_and_hms(2026, 11, 7, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            usr.next_occurrence(Day::Sun, 20 * 60, now),
+            Utc.with_ymd_and_hms(2026, 11, 2, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            usr.next_occurrence(Day::Sun, 18 * 60, now),
+            Utc.with_ymd_and_hms(2026, 11, 9, 2, 0, 0).unwrap()
+        );
+        // Daylight saving time has not ended yet on 2026-10-31.
+        assert_eq!(
+            usr.date_to_utc(NaiveDate::from_ymd_opt(2026, 10, 31).unwrap(), 20 * 60),
+            Utc.with_ymd_and_hms(2026, 11, 1, 3, 0, 0).unwrap()
+        );
     }
 }